@@ -0,0 +1,70 @@
+use specs::prelude::*;
+use super::{CombatStats, Abilities, AbilityEffect, WantsToUseAbility, Name, SufferDamage, Position,
+    Map, gamelog::Gamelog, particle_system::ParticleBuilder};
+use rltk::{Point, RGB};
+
+pub struct AbilitySystem {}
+
+impl<'a> System<'a> for AbilitySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Map>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteStorage<'a, WantsToUseAbility>,
+                        ReadStorage<'a, Abilities>,
+                        WriteStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Position>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, ParticleBuilder>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, map, player_entity, mut log, mut wants_ability, abilities, mut combat_stats,
+            names, positions, mut inflict_damage, mut particles) = data;
+
+        for (entity, intent, abilities, name) in (&entities, &wants_ability, &abilities, &names).join() {
+            let caster_alive = combat_stats.get(entity).is_some_and(|stats| stats.hp > 0);
+            let target_alive = combat_stats.get(intent.target).is_some_and(|stats| stats.hp > 0);
+            if !caster_alive || !target_alive {continue;}
+
+            let ability = &abilities.list[intent.ability];
+            let target_name = &names.get(intent.target).unwrap().name;
+
+            // Only report what the player could witness
+            let seen = intent.target == *player_entity || positions.get(entity).is_some_and(|pos| {
+                map.visible_tiles[map.xy_idx(pos.x, pos.y)]
+            });
+
+            let from = positions.get(entity).map(|pos| Point::new(pos.x, pos.y));
+            let to = positions.get(intent.target).map(|pos| Point::new(pos.x, pos.y));
+
+            match ability.effect {
+                AbilityEffect::Damage{amount, kind} => {
+                    if let (Some(from), Some(to)) = (from, to) {
+                        particles.trail(from, to, RGB::named(rltk::CYAN), rltk::to_cp437('*'));
+                        particles.flash(to.x, to.y, RGB::named(rltk::ORANGE));
+                    }
+                    SufferDamage::new_damage(&mut inflict_damage, intent.target, amount, kind, Some(entity), &ability.name);
+                    if seen {
+                        log.entries.push(format!("{} {} {}, for {} {} damage", &name.name, &ability.verb, target_name,
+                            amount, kind.name()));
+                    }
+                }
+                AbilityEffect::HealAlly{amount} => {
+                    let stats = combat_stats.get_mut(intent.target).unwrap();
+                    stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+                    if let Some(to) = to {
+                        particles.request(to.x, to.y, RGB::named(rltk::GREEN), RGB::named(rltk::BLACK),
+                            rltk::to_cp437('♥'), 0.0, 200.0);
+                    }
+                    if seen {
+                        log.entries.push(format!("{} {} {}, healing {} hp", &name.name, &ability.verb, target_name, amount));
+                    }
+                }
+            }
+        }
+
+        wants_ability.clear();
+    }
+}
\ No newline at end of file
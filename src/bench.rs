@@ -0,0 +1,110 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use super::{Map, TileType, RunState, Awareness, AwarenessState, Alerted, Monster, Position, Fov, spawner, gamelog::Gamelog,
+    particle_system::ParticleBuilder, noise_system::NoiseEvents, fov::FovSystem, monster_ai_system::{MonsterAI, ChasePathing},
+    map_indexing::MapIndexSystem, register_components};
+
+const TURNS : u32 = 50;
+const MAPS : u32 = 5;
+
+/// Times the per-turn FOV and monster AI pipeline with every monster hunting the player, once with a
+/// separate A* search per monster and once with the shared chase map the game uses.
+/// Run with `cargo run --release -- --bench-ai`.
+pub fn run() {
+    println!("monsters   per-monster A*   shared chase map");
+    for count in [50, 100, 200, 400].iter() {
+        let mut searched = 0.0;
+        let mut shared = 0.0;
+        for trial in 0..MAPS {
+            searched += time_level(*count, trial, ChasePathing::PerMonster);
+            shared += time_level(*count, trial, ChasePathing::Shared);
+        }
+        println!("{:>8} {:>13.2} ms {:>15.2} ms", count, searched / MAPS as f64, shared / MAPS as f64);
+    }
+}
+
+/// Average milliseconds per turn on one freshly generated level
+fn time_level(count : i32, trial : u32, pathing : ChasePathing) -> f64 {
+    let seed = (count as u32 * MAPS + trial) as u64;
+    let mut ecs = World::new();
+    register_components(&mut ecs);
+    let mut rng = RandomNumberGenerator::seeded(seed);
+    let map = Map::new_map_rooms_and_corridors(1, &mut rng);
+    ecs.insert(rng);
+
+    let (player_x, player_y) = map.rooms[0].center();
+    let player = Point::new(player_x, player_y);
+    let player_entity = spawner::player(&mut ecs, player_x, player_y);
+
+    // Floor the monsters can be dropped on, out of the player's reach so nobody just attacks
+    let floor : Vec<usize> = map.tiles.iter().enumerate()
+        .filter(|(idx, tile)| **tile == TileType::Floor
+            && rltk::DistanceAlg::Pythagoras.distance2d(Point::new(*idx as i32 % map.width, *idx as i32 / map.width), player) >= 2.0)
+        .map(|(idx, _)| idx)
+        .collect();
+    for _ in 0..count {
+        spawner::spawn_entity(&mut ecs, "Goblin", player_x, player_y);
+    }
+
+    let width = map.width;
+    ecs.insert(map);
+    ecs.insert(player);
+    ecs.insert(player_entity);
+    ecs.insert(RunState::MonsterTurn);
+    ecs.insert(NoiseEvents::default());
+    ecs.insert(ParticleBuilder::default());
+    ecs.insert(Gamelog{entries: Vec::new()});
+
+    {
+        let entities = ecs.entities();
+        let monsters = ecs.read_storage::<Monster>();
+        let mut awareness = ecs.write_storage::<Awareness>();
+        for (entity, _monster) in (&entities, &monsters).join() {
+            awareness.insert(entity, Awareness{state: AwarenessState::Aware}).expect("Unable to insert awareness");
+        }
+    }
+
+    // Both kinds of pathing get the same scatter, so they chase from the same places
+    let mut scatter_rng = RandomNumberGenerator::seeded(seed);
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..TURNS {
+        scatter(&mut ecs, &floor, width, &mut scatter_rng);
+        let start = Instant::now();
+        FovSystem{}.run_now(&ecs);
+        MonsterAI{pathing}.run_now(&ecs);
+        MapIndexSystem{}.run_now(&ecs);
+        elapsed += start.elapsed();
+        ecs.maintain();
+    }
+    elapsed.as_secs_f64() * 1000.0 / TURNS as f64
+}
+
+/// Drops every monster on a different random floor tile, still hunting the player. Left alone they
+/// would bunch up next to the player within a few turns and stop pathfinding altogether.
+/// Only walls block at the start of the turn: at a few hundred monsters they would otherwise plug the
+/// corridors, and most searches would end early with the player out of reach.
+fn scatter(ecs : &mut World, floor : &[usize], width : i32, rng : &mut RandomNumberGenerator) {
+    {
+        let player = *ecs.fetch::<Point>();
+        let entities = ecs.entities();
+        let monsters = ecs.read_storage::<Monster>();
+        let mut positions = ecs.write_storage::<Position>();
+        let mut fovs = ecs.write_storage::<Fov>();
+        let mut alerted = ecs.write_storage::<Alerted>();
+        let mut taken : HashSet<usize> = HashSet::new();
+        for (entity, _monster, pos, fov) in (&entities, &monsters, &mut positions, &mut fovs).join() {
+            let idx = loop {
+                let idx = floor[rng.roll_dice(1, floor.len() as i32) as usize - 1];
+                if taken.insert(idx) {break idx;}
+            };
+            pos.x = idx as i32 % width;
+            pos.y = idx as i32 / width;
+            fov.dirty = true;
+            alerted.insert(entity, Alerted{last_seen: player}).expect("Unable to insert alert");
+        }
+    }
+    MapIndexSystem{}.run_now(ecs);
+    ecs.fetch_mut::<Map>().populate_blocked();
+}
\ No newline at end of file
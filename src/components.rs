@@ -1,6 +1,7 @@
 use specs::prelude::*;
 use specs_derive::*;
-use rltk::{RGB};
+use rltk::{RGB, RandomNumberGenerator};
+use serde::Deserialize;
 
 #[derive(Component)]
 pub struct Position {
@@ -21,11 +22,23 @@ pub struct Player {}
 
 #[derive(Component)]
 pub struct Fov {
-	pub visible_tiles : Vec<rltk::Point>,
+	/// Map indices in sight
+	pub visible : BitSet,
 	pub range : i32,
 	pub dirty : bool
 }
 
+impl Fov {
+	pub fn new(range : i32) -> Fov {
+		Fov{visible : BitSet::new(), range, dirty : true}
+	}
+
+	/// Constant time lookup by map index
+	pub fn can_see(&self, idx : usize) -> bool {
+		self.visible.contains(idx as u32)
+	}
+}
+
 #[derive(Component, Debug)]
 pub struct Monster {}
 
@@ -37,12 +50,70 @@ pub struct Name {
 #[derive(Component, Debug)]
 pub struct BlocksTile {}
 
+/// Dice such as 1d6+2, rolled with the world RNG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiceRoll {
+	pub n_dice : i32,
+	pub die_type : i32,
+	pub bonus : i32
+}
+
+impl DiceRoll {
+	pub fn new(n_dice : i32, die_type : i32, bonus : i32) -> DiceRoll {
+		DiceRoll{n_dice, die_type, bonus}
+	}
+
+	pub fn roll(&self, rng : &mut RandomNumberGenerator) -> i32 {
+		rng.roll_dice(self.n_dice, self.die_type) + self.bonus
+	}
+}
+
+impl std::fmt::Display for DiceRoll {
+	fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self.bonus {
+			0 => write!(f, "{}d{}", self.n_dice, self.die_type),
+			b if b > 0 => write!(f, "{}d{}+{}", self.n_dice, self.die_type, b),
+			b => write!(f, "{}d{}{}", self.n_dice, self.die_type, b)
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DamageType { Physical, Fire, Cold, Poison, Lightning }
+
+impl DamageType {
+	pub fn name(&self) -> &'static str {
+		match self {
+			DamageType::Physical => "physical",
+			DamageType::Fire => "fire",
+			DamageType::Cold => "cold",
+			DamageType::Poison => "poison",
+			DamageType::Lightning => "lightning"
+		}
+	}
+}
+
+/// `defense` is armor, which turns aside blows that get past evasion.
+/// `power` is added to attack rolls, and `damage` is rolled when unarmed.
 #[derive(Component, Debug)]
 pub struct CombatStats {
 	pub max_hp : i32,
 	pub hp : i32,
 	pub defense : i32,
-	pub power : i32
+	pub power : i32,
+	pub damage : DiceRoll,
+	pub damage_type : DamageType
+}
+
+/// Resistant halves a damage type, immune ignores it and vulnerable doubles it.
+/// Worn on a creature, or on equipment for whoever has it equipped.
+#[derive(Component, Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Resistances {
+	pub resistant : Vec<DamageType>,
+	pub immune : Vec<DamageType>,
+	pub vulnerable : Vec<DamageType>
 }
 
 #[derive(Component, Debug, Clone)]
@@ -52,20 +123,152 @@ pub struct AttackIntent {
 
 #[derive(Component, Debug)]
 pub struct SufferDamage {
-	pub amount : Vec<i32>
+	pub amount : Vec<Hit>
+}
+
+/// One instance of damage, with who dealt it and how
+#[derive(Debug, Clone)]
+pub struct Hit {
+	pub amount : i32,
+	pub kind : DamageType,
+	pub source : Option<Entity>,
+	pub cause : String
 }
 
 impl SufferDamage {
-	pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+	pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, kind: DamageType,
+		source: Option<Entity>, cause: &str)
+	{
+		let hit = Hit{amount, kind, source, cause: cause.to_string()};
 		if let Some(suffering) = store.get_mut(victim) {
-			suffering.amount.push(amount);
+			suffering.amount.push(hit);
 		} else {
-			let dmg = SufferDamage{amount: vec![amount]};
+			let dmg = SufferDamage{amount: vec![hit]};
 			store.insert(victim, dmg).expect("Unable to insert damage");
 		}
 	}
 }
 
+/// Progress toward the next character level, which needs `experience::xp_to_next` points.
+/// `picks` counts level-up choices not yet made.
+#[derive(Component, Debug, Clone)]
+pub struct Experience {
+	pub level : i32,
+	pub xp : i32,
+	pub picks : i32
+}
+
+/// Experience awarded to whoever lands the killing blow
+#[derive(Component, Debug, Clone)]
+pub struct XpValue {
+	pub xp : i32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perk { KeenEye, Steady }
+
+/// Perks picked on level up. Keen Eye crits on 19 or 20, Steady never fumbles.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Perks {
+	pub list : Vec<Perk>
+}
+
+/// The last thing to hurt an entity, kept for kill credit and death causes.
+/// `killer` is already worded for a sentence, e.g. "a Goblin".
+#[derive(Component, Debug, Clone)]
+pub struct LastHit {
+	pub source : Option<Entity>,
+	pub killer : String,
+	pub cause : String
+}
+
+/// Pushes an entity `distance` tiles along (dx, dy); resolved by ForcedMovementSystem
+#[derive(Component, Debug, Clone)]
+pub struct ForcedMove {
+	pub dx : i32,
+	pub dy : i32,
+	pub distance : i32,
+	pub source : Option<Entity>
+}
+
+/// Heavy weapons that drive their target back on a hit
+#[derive(Component, Debug, Clone)]
+pub struct Knockback {
+	pub distance : i32
+}
+
+/// Creatures the player trades places with instead of attacking
+#[derive(Component, Debug)]
+pub struct Friendly {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HungerState { WellFed, Normal, Hungry, Starving }
+
+/// Where the player is on the road to starving, with `duration` turns left in the current state
+#[derive(Component, Debug, Clone)]
+pub struct HungerClock {
+	pub state : HungerState,
+	pub duration : i32
+}
+
+/// Items that can be eaten to become well fed
+#[derive(Component, Debug)]
+pub struct ProvidesFood {}
+
+/// Identical items carried as one entity, `count` of them
+#[derive(Component, Debug, Clone)]
+pub struct Stack {
+	pub count : i32
+}
+
+/// How heavy one of an item is, in pounds
+#[derive(Component, Debug, Clone)]
+pub struct Weight {
+	pub weight : f32
+}
+
+/// What a creature carries against what it can. `lagging` is set by a step taken while overburdened,
+/// which costs an extra monster turn.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Encumbrance {
+	pub carried : f32,
+	pub capacity : f32,
+	pub lagging : bool
+}
+
+impl Encumbrance {
+	pub fn overburdened(&self) -> bool {
+		self.carried > self.capacity
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind { Rest }
+
+/// A command the player repeats turn after turn until it finishes or something interrupts it.
+/// The `last_*` fields are what the previous turn ended with, to notice changes.
+#[derive(Component, Debug, Clone)]
+pub struct OngoingAction {
+	pub kind : ActionKind,
+	pub turns_left : i32,
+	pub last_hp : i32,
+	pub last_hunger : Option<HungerState>,
+	pub last_level : i32
+}
+
+/// A short-lived visual effect, hidden until `delay_ms` has passed and gone `lifetime_ms` after
+#[derive(Component, Debug)]
+pub struct ParticleLifetime {
+	pub delay_ms : f32,
+	pub lifetime_ms : f32
+}
+
+/// What a monster leaves behind, gone after `turns_left` monster turns
+#[derive(Component, Debug)]
+pub struct Corpse {
+	pub turns_left : i32
+}
+
 #[derive(Component, Debug)]
 pub struct Item {}
 
@@ -90,14 +293,142 @@ pub struct WantsToDrinkPotion {
 	pub potion : Entity
 }
 
+/// Reading a scroll, aimed at `target` when the scroll is Ranged
+#[derive(Component, Debug)]
+pub struct WantsToReadScroll {
+	pub scroll : Entity,
+	pub target : Option<rltk::Point>
+}
+
+/// Single use items read from the inventory. What they do comes from the effect components below.
+#[derive(Component, Debug)]
+pub struct Scroll {}
+
+/// Reveals the whole level
+#[derive(Component, Debug)]
+pub struct MagicMapper {}
+
+/// Moves the reader somewhere random they could walk to
+#[derive(Component, Debug)]
+pub struct Teleports {}
+
+/// Identifies everything the reader carries
+#[derive(Component, Debug)]
+pub struct Identifies {}
+
+/// Lifts the curse from everything the reader carries or wears
+#[derive(Component, Debug)]
+pub struct RemovesCurse {}
+
+/// Needs a target tile no more than `range` away
+#[derive(Component, Debug)]
+pub struct Ranged {
+	pub range : i32
+}
+
+/// Hits everything within `radius` of the target
+#[derive(Component, Debug)]
+pub struct AreaOfEffect {
+	pub radius : i32
+}
+
+#[derive(Component, Debug)]
+pub struct InflictsDamage {
+	pub damage : DiceRoll,
+	pub kind : DamageType
+}
+
+/// A reusable magic device. Once out of charges it crumbles away if `crumbles`, or just stops working.
+#[derive(Component, Debug)]
+pub struct Wand {
+	pub charges : i32,
+	pub crumbles : bool
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BoltEffect {
+	Damage { damage : DiceRoll, kind : DamageType },
+	Slow { turns : i32 },
+	Dig
+}
+
+/// What a wand fires along a line as far as its Ranged range
+#[derive(Component, Debug)]
+pub struct Bolt {
+	pub effect : BoltEffect
+}
+
+#[derive(Component, Debug)]
+pub struct WantsToZap {
+	pub wand : Entity,
+	pub target : rltk::Point
+}
+
+/// Opened by bumping into it to take what is inside. A locked one needs a key or brute force first.
+#[derive(Component, Debug)]
+pub struct Container {
+	pub locked : bool
+}
+
+/// Goes off at whoever opens the container, once
+#[derive(Component, Debug)]
+pub struct Trapped {
+	pub name : String,
+	pub damage : DiceRoll,
+	pub kind : DamageType
+}
+
+/// An item lying inside a container
+#[derive(Component, Debug)]
+pub struct InContainer {
+	pub container : Entity
+}
+
+/// Unlocks a container, and is used up doing it
+#[derive(Component, Debug)]
+pub struct Key {}
+
+/// A pile of coins, scooped up by anyone with a purse who walks over it
+#[derive(Component, Debug)]
+pub struct Gold {
+	pub amount : i32
+}
+
+#[derive(Component, Debug, Default)]
+pub struct Purse {
+	pub gold : i32
+}
+
+/// Trades with the player when bumped. Without a Monster component the AI leaves them be until provoked.
+#[derive(Component, Debug)]
+pub struct Merchant {}
+
+/// Acts only every other turn until `turns` run out
+#[derive(Component, Debug)]
+pub struct Slowed {
+	pub turns : i32
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct WantsToDropItem {
 	pub item : Entity
 }
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EquipmentSlot { Weapon, Shield }
 
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Blessing { Cursed, Blessed }
+
+/// Cursed gear sticks to whoever equips it and drags its bonuses down, blessed gear lifts them.
+/// Which one it is stays hidden until `known`, once worn or identified.
+#[derive(Component, Debug)]
+pub struct CurseStatus {
+	pub blessing : Blessing,
+	pub known : bool
+}
+
 #[derive(Component, Clone)]
 pub struct Equippable {
 	pub slot : EquipmentSlot
@@ -107,4 +438,84 @@ pub struct Equippable {
 pub struct Equipped {
 	pub owner : Entity,
 	pub slot : EquipmentSlot
-}
\ No newline at end of file
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum AbilityEffect { Damage{amount : i32, kind : DamageType}, HealAlly{amount : i32} }
+
+/// A monster ability with a range in tiles and a cooldown in monster turns
+#[derive(Clone, Debug)]
+pub struct Ability {
+	pub name : String,
+	pub verb : String,
+	pub effect : AbilityEffect,
+	pub range : f32,
+	pub cooldown : i32,
+	pub turns_until_ready : i32
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Abilities {
+	pub list : Vec<Ability>
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct WantsToUseAbility {
+	pub ability : usize,
+	pub target : Entity
+}
+
+/// Monsters spawned together share a pack id and hunt as a group
+#[derive(Component, Debug, Clone)]
+pub struct Pack {
+	pub id : u32
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Alerted {
+	pub last_seen : rltk::Point
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct MeleePowerBonus {
+	pub power : i32
+}
+
+/// Dice a weapon rolls in place of its wielder's own
+#[derive(Component, Debug, Clone)]
+pub struct WeaponDamage {
+	pub dice : DiceRoll,
+	pub kind : DamageType
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct DefenseBonus {
+	pub defense : i32
+}
+
+/// Monsters that pick up, drink and equip items
+#[derive(Component, Debug)]
+pub struct UsesItems {}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AwarenessState { Asleep, Unaware, Aware }
+
+/// Whether a monster has noticed the player yet
+#[derive(Component, Debug, Clone)]
+pub struct Awareness {
+	pub state : AwarenessState
+}
+
+
+/// A path to a goal other than the player, kept between turns so it is not searched for again.
+/// Steps are stored in reverse so the next one can be popped off the end.
+#[derive(Component, Debug, Clone)]
+pub struct PathCache {
+	pub goal : usize,
+	pub steps : Vec<usize>
+}
+
+/// A named unique monster that guards its depth
+#[derive(Component, Debug)]
+pub struct Boss {}
\ No newline at end of file
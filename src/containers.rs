@@ -0,0 +1,104 @@
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+use super::{Container, Trapped, InContainer, Key, InBackpack, Name, Position, CombatStats, SufferDamage, RunState,
+    gamelog::Gamelog, noise_system::{NoiseEvents, COMBAT_NOISE}, particle_system::ParticleBuilder, inventory_system::use_up, Stack, Gold, Purse, Item};
+
+/// What forcing a lock has to beat on a d20 plus the player's power
+const FORCE_LOCK_DC : i32 = 18;
+
+/// The player bumps into a container: unlock it, spring its trap, then look inside
+pub fn open(ecs : &mut World, container : Entity) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = ecs.read_storage::<Name>().get(container).map_or("chest".to_string(), |n| n.name.clone());
+
+    let locked = ecs.read_storage::<Container>().get(container).is_some_and(|c| c.locked);
+    if locked && !unlock(ecs, player_entity, container, &name) {
+        return RunState::PlayerTurn;
+    }
+
+    if let Some(trap) = ecs.write_storage::<Trapped>().remove(container) {
+        let amount = {
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            trap.damage.roll(&mut rng)
+        };
+        let mut damage = ecs.write_storage::<SufferDamage>();
+        SufferDamage::new_damage(&mut damage, player_entity, amount, trap.kind, None, &trap.name);
+        ecs.fetch_mut::<Gamelog>().entries.push(format!("A {} springs from the {}! You take {} damage.", trap.name, name, amount));
+        if let Some(pos) = ecs.read_storage::<Position>().get(container) {
+            ecs.write_resource::<ParticleBuilder>().flash(pos.x, pos.y, RGB::named(rltk::RED));
+        }
+        return RunState::PlayerTurn;
+    }
+
+    take_gold(ecs, player_entity, container);
+    let empty = !(&ecs.read_storage::<Item>(), &ecs.read_storage::<InContainer>()).join().any(|(_item, inside)| inside.container == container);
+    if empty {
+        ecs.fetch_mut::<Gamelog>().entries.push(format!("The {} is empty.", name));
+        return RunState::PlayerTurn;
+    }
+    RunState::ShowContainer{container, page: 0}
+}
+
+/// Coins inside go straight into the opener's purse
+fn take_gold(ecs : &mut World, opener : Entity, container : Entity) {
+    let entities = ecs.entities();
+    let mut inside = ecs.write_storage::<InContainer>();
+    let gold = ecs.read_storage::<Gold>();
+    let mut purses = ecs.write_storage::<Purse>();
+    let Some(purse) = purses.get_mut(opener) else {return};
+
+    let piles : Vec<(Entity, i32)> = (&entities, &inside, &gold).join()
+        .filter(|(_pile, held, _coins)| held.container == container)
+        .map(|(pile, _held, coins)| (pile, coins.amount))
+        .collect();
+    // Deletion only lands at the next maintain, so take the piles out of the chest now or they get counted again
+    let mut found = 0;
+    for (pile, amount) in piles {
+        inside.remove(pile);
+        entities.delete(pile).expect("Unable to delete gold");
+        found += amount;
+    }
+    if found > 0 {
+        purse.gold += found;
+        ecs.fetch_mut::<Gamelog>().entries.push(format!("You find {} gold inside.", found));
+    }
+}
+
+/// Spends a carried key on the lock, or tries to break it open. True once it is open.
+fn unlock(ecs : &mut World, player_entity : Entity, container : Entity, name : &str) -> bool {
+    let key = {
+        let keys = ecs.read_storage::<Key>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let entities = ecs.entities();
+        (&entities, &keys, &backpack).join().find(|(_item, _key, pack)| pack.owner == player_entity).map(|(item, ..)| item)
+    };
+
+    let opened = match key {
+        Some(key) => {
+            let entities = ecs.entities();
+            let mut stacks = ecs.write_storage::<Stack>();
+            use_up(&entities, &mut stacks, key);
+            ecs.fetch_mut::<Gamelog>().entries.push(format!("You unlock the {} with a key.", name));
+            true
+        }
+        None => {
+            let power = ecs.read_storage::<CombatStats>().get(player_entity).map_or(0, |stats| stats.power);
+            let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 20) + power;
+            // Breaking a lock is loud whether it works or not
+            if let Some(pos) = ecs.read_storage::<Position>().get(container) {
+                ecs.write_resource::<NoiseEvents>().emit(pos.x, pos.y, COMBAT_NOISE);
+            }
+            let forced = roll >= FORCE_LOCK_DC;
+            let message = if forced {format!("You force the {} open!", name)} else {format!("The {} is locked. You fail to force it.", name)};
+            ecs.fetch_mut::<Gamelog>().entries.push(message);
+            forced
+        }
+    };
+
+    if opened {
+        if let Some(chest) = ecs.write_storage::<Container>().get_mut(container) {
+            chest.locked = false;
+        }
+    }
+    opened
+}
\ No newline at end of file
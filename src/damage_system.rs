@@ -1,34 +1,121 @@
 use specs::prelude::*;
-use super::{CombatStats, SufferDamage, Player, Name, gamelog::Gamelog};
+use super::{CombatStats, SufferDamage, Player, Name, gamelog::Gamelog, Position, InBackpack, Equipped,
+    Resistances, DamageType, Map, Renderable, Corpse, spawner, LastHit, Boss, gamelog::RunStats, Experience, XpValue, RunState, experience};
 
 
 pub struct DamageSystem {}
 
+/// How a victim's own and equipped resistances change one hit
+#[derive(PartialEq)]
+enum Modifier { Normal, Resisted, Immune, Vulnerable }
+
+fn modifier(kind : DamageType, worn : &[&Resistances]) -> Modifier {
+    let has = |list : fn(&Resistances) -> &Vec<DamageType>| worn.iter().any(|r| list(r).contains(&kind));
+    if has(|r| &r.immune) {
+        return Modifier::Immune;
+    }
+    match (has(|r| &r.resistant), has(|r| &r.vulnerable)) {
+        (true, false) => Modifier::Resisted,
+        (false, true) => Modifier::Vulnerable,
+        _ => Modifier::Normal
+    }
+}
+
 impl<'a> System<'a> for DamageSystem {
-    type SystemData = ( WriteStorage<'a, CombatStats>,
-                        WriteStorage<'a, SufferDamage>);
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Map>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, LastHit>,
+                        ReadStorage<'a, Boss>,
+                        ReadStorage<'a, Resistances>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Position>);
 
-    // Damage vector
+    // Damage vector, after resistances
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (entities, map, player_entity, mut log, mut stats, mut damage, mut last_hits, bosses, resistances, equipped,
+            names, positions) = data;
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+        for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
+            let mut worn : Vec<&Resistances> = (&equipped, &resistances).join()
+                .filter(|(eq, _)| eq.owner == entity)
+                .map(|(_, res)| res)
+                .collect();
+            if let Some(own) = resistances.get(entity) {
+                worn.push(own);
+            }
+
+            let seen = entity == *player_entity || positions.get(entity).is_some_and(|pos| {
+                map.visible_tiles[map.xy_idx(pos.x, pos.y)]
+            });
+            let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+
+            for hit in damage.amount.iter() {
+                let (amount, kind) = (hit.amount, hit.kind);
+                let modifier = modifier(kind, &worn);
+                stats.hp -= match modifier {
+                    Modifier::Normal => amount,
+                    Modifier::Resisted => amount / 2,
+                    Modifier::Immune => 0,
+                    Modifier::Vulnerable => amount * 2
+                };
+
+                let killer = match hit.source {
+                    None => hit.cause.clone(),
+                    Some(source) if source == *player_entity => "you".to_string(),
+                    Some(source) => match names.get(source) {
+                        None => "something".to_string(),
+                        Some(n) if bosses.get(source).is_some() => n.name.clone(),
+                        Some(n) => format!("{} {}", article(&n.name), n.name)
+                    }
+                };
+                last_hits.insert(entity, LastHit{source: hit.source, killer, cause: hit.cause.clone()})
+                    .expect("Unable to record hit");
+
+                if seen {
+                    match modifier {
+                        Modifier::Normal => {}
+                        Modifier::Resisted => log.entries.push(format!("{} resists the {}", name, kind.name())),
+                        Modifier::Immune => log.entries.push(format!("{} is immune to {}", name, kind.name())),
+                        Modifier::Vulnerable => log.entries.push(format!("{} is badly hurt by the {}!", name, kind.name()))
+                    }
+                }
+            }
         }
 
         damage.clear();
     }
 }
 
+fn article(name : &str) -> &'static str {
+    match name.chars().next().map(|c| c.to_ascii_lowercase()) {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+        _ => "a"
+    }
+}
+
 pub fn corpse_removal(ecs : &mut World) {
     let mut dead : Vec<Entity> = Vec::new();
     // Using a scope to keep borrow checker happy
     {
-        let combat_stats = ecs.read_storage::<CombatStats>();
+        let mut combat_stats = ecs.write_storage::<CombatStats>();
+        let mut experience = ecs.write_storage::<Experience>();
+        let xp_values = ecs.read_storage::<XpValue>();
+        let mut runstate = ecs.write_resource::<RunState>();
         let players = ecs.read_storage::<Player>();
         let names = ecs.read_storage::<Name>();
+        let last_hits = ecs.read_storage::<LastHit>();
         let entities = ecs.entities();
+        let player_entity = ecs.fetch::<Entity>();
+        let depth = ecs.fetch::<Map>().depth;
         let mut log = ecs.write_resource::<Gamelog>();
+        let mut run_stats = ecs.write_resource::<RunStats>();
+        let mut credits : Vec<(Entity, i32)> = Vec::new();
         for (entity, stats) in (&entities, &combat_stats).join() {
             if stats.hp < 1 {
                 let player = players.get(entity);
@@ -38,15 +125,102 @@ pub fn corpse_removal(ecs : &mut World) {
                         if let Some(victim_name) = victim_name {
                             log.entries.push(format!("{} is dead", &victim_name.name));
                         }
+                        if let Some(killer) = last_hits.get(entity).and_then(|hit| hit.source) {
+                            if killer == *player_entity {
+                                run_stats.kills += 1;
+                            }
+                            if let Some(value) = xp_values.get(entity) {
+                                credits.push((killer, value.xp));
+                            }
+                        }
                         dead.push(entity)
                     }
-                    Some(_) => log.entries.push(format!("You died"))
+                    Some(_) => {
+                        if run_stats.cause_of_death.is_none() {
+                            let cause = match last_hits.get(entity) {
+                                None => format!("Died on depth {}", depth),
+                                Some(hit) if hit.source.is_none() => format!("Killed by {} on depth {}", hit.killer, depth),
+                                Some(hit) => format!("Killed by {} ({}) on depth {}", hit.killer, hit.cause, depth)
+                            };
+                            log.entries.push(format!("You died. {}.", cause));
+                            run_stats.cause_of_death = Some(cause);
+                            *runstate = RunState::GameOver;
+                        }
+                    }
                 }
             }
         }
+
+        for (killer, xp) in credits {
+            let (Some(exp), Some(stats)) = (experience.get_mut(killer), combat_stats.get_mut(killer)) else {continue};
+            if stats.hp > 0 && experience::award_xp(exp, stats, xp) && killer == *player_entity {
+                log.entries.push(format!("Welcome to level {}!", exp.level));
+                *runstate = RunState::LevelUp;
+            }
+        }
     }
 
     for victim in dead {
+        drop_belongings(ecs, victim);
+        leave_remains(ecs, victim);
         ecs.delete_entity(victim).expect("Unable to delete");
     }
+}
+
+/// Puts a corpse and any rolled loot where the victim fell
+fn leave_remains(ecs : &mut World, victim : Entity) {
+    let remains = {
+        let positions = ecs.read_storage::<Position>();
+        let names = ecs.read_storage::<Name>();
+        let renderables = ecs.read_storage::<Renderable>();
+        match (positions.get(victim), names.get(victim), renderables.get(victim)) {
+            (Some(pos), Some(name), Some(render)) => Some((pos.x, pos.y, name.name.clone(), render.fg)),
+            _ => None
+        }
+    };
+
+    if let Some((x, y, name, fg)) = remains {
+        spawner::corpse(ecs, x, y, &name, fg);
+        spawner::roll_loot(ecs, &name, x, y);
+    }
+}
+
+/// Ages every corpse by a turn, clearing away the ones that have rotted
+pub fn decay_corpses(ecs : &mut World) {
+    let rotted : Vec<Entity> = {
+        let entities = ecs.entities();
+        let mut corpses = ecs.write_storage::<Corpse>();
+        (&entities, &mut corpses).join()
+            .filter_map(|(entity, corpse)| {
+                corpse.turns_left -= 1;
+                if corpse.turns_left <= 0 {Some(entity)} else {None}
+            })
+            .collect()
+    };
+    for corpse in rotted {
+        ecs.delete_entity(corpse).expect("Unable to delete");
+    }
+}
+
+/// Leaves everything the victim carried or wore on the tile where it fell
+fn drop_belongings(ecs : &mut World, victim : Entity) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut equipped = ecs.write_storage::<Equipped>();
+
+    let (x, y) = match positions.get(victim) {
+        None => return,
+        Some(pos) => (pos.x, pos.y)
+    };
+
+    let belongings : Vec<Entity> = (&entities).join()
+        .filter(|item| backpack.get(*item).is_some_and(|pack| pack.owner == victim) ||
+            equipped.get(*item).is_some_and(|worn| worn.owner == victim))
+        .collect();
+    for item in belongings {
+        backpack.remove(item);
+        equipped.remove(item);
+        positions.insert(item, Position{x, y}).expect("Unable to drop item");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+use specs::prelude::*;
+use super::{CombatStats, Encumbrance, InBackpack, Equipped, Weight, Stack, gamelog::Gamelog};
+
+const BASE_CAPACITY : f32 = 10.0;
+const CAPACITY_PER_POWER : f32 = 4.0;
+
+/// Pounds a creature can carry before it slows down, which grows with its power
+pub fn carry_capacity(stats : &CombatStats) -> f32 {
+    BASE_CAPACITY + stats.power as f32 * CAPACITY_PER_POWER
+}
+
+/// Weighs everything each creature carries or wears against its capacity
+pub struct EncumbranceSystem {}
+
+impl<'a> System<'a> for EncumbranceSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Gamelog>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, InBackpack>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Weight>,
+                        ReadStorage<'a, Stack>,
+                        WriteStorage<'a, Encumbrance>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, mut gamelog, combat_stats, backpack, equipped, weights, stacks, mut encumbrance) = data;
+
+        for (entity, stats, load) in (&entities, &combat_stats, &mut encumbrance).join() {
+            let carried : f32 = (&weights, stacks.maybe(), backpack.maybe(), equipped.maybe()).join()
+                .filter(|(_w, _s, pack, worn)| pack.is_some_and(|p| p.owner == entity) || worn.is_some_and(|w| w.owner == entity))
+                .map(|(weight, stack, _p, _w)| weight.weight * stack.map_or(1, |s| s.count) as f32)
+                .sum();
+
+            let was_overburdened = load.overburdened();
+            load.carried = carried;
+            load.capacity = carry_capacity(stats);
+            if !load.overburdened() {
+                load.lagging = false;
+            }
+
+            if entity == *player_entity && was_overburdened != load.overburdened() {
+                if load.overburdened() {
+                    gamelog.entries.push("You are overburdened and struggle to move.".to_string());
+                } else {
+                    gamelog.entries.push("You are no longer overburdened.".to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Whether the player's last step was slowed by their load, giving monsters another turn. Clears the slowdown.
+pub fn lags_behind(ecs : &mut World) -> bool {
+    let player_entity = ecs.fetch::<Entity>();
+    let mut encumbrance = ecs.write_storage::<Encumbrance>();
+    match encumbrance.get_mut(*player_entity) {
+        Some(load) if load.lagging => {
+            load.lagging = false;
+            true
+        }
+        _ => false
+    }
+}
\ No newline at end of file
@@ -0,0 +1,81 @@
+use specs::prelude::*;
+use super::{CombatStats, Experience, Perk, Perks, gamelog::Gamelog};
+
+/// Max HP every level grants before the player's own pick
+const HP_PER_LEVEL : i32 = 5;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpChoice { Vitality, Might, Guard, Learn(Perk) }
+
+impl LevelUpChoice {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            LevelUpChoice::Vitality => "Vitality: +8 max HP",
+            LevelUpChoice::Might => "Might: +1 to attack rolls",
+            LevelUpChoice::Guard => "Guard: +1 armor",
+            LevelUpChoice::Learn(Perk::KeenEye) => "Keen Eye: critical hits on 19 or 20",
+            LevelUpChoice::Learn(Perk::Steady) => "Steady: never fumble an attack"
+        }
+    }
+}
+
+pub fn xp_to_next(level : i32) -> i32 {
+    level * 50
+}
+
+/// Adds experience, returning whether a level was gained
+pub fn award_xp(exp : &mut Experience, stats : &mut CombatStats, amount : i32) -> bool {
+    exp.xp += amount;
+    let mut levelled = false;
+    while exp.xp >= xp_to_next(exp.level) {
+        exp.xp -= xp_to_next(exp.level);
+        exp.level += 1;
+        exp.picks += 1;
+        stats.max_hp += HP_PER_LEVEL;
+        stats.hp += HP_PER_LEVEL;
+        levelled = true;
+    }
+    levelled
+}
+
+/// What the player may pick from, leaving out perks already learned
+pub fn choices(ecs : &World) -> Vec<LevelUpChoice> {
+    let player_entity = ecs.fetch::<Entity>();
+    let perks = ecs.read_storage::<Perks>();
+    let known = perks.get(*player_entity).map_or(Vec::new(), |p| p.list.clone());
+
+    let mut choices = vec![LevelUpChoice::Vitality, LevelUpChoice::Might, LevelUpChoice::Guard];
+    for perk in [Perk::KeenEye, Perk::Steady] {
+        if !known.contains(&perk) {
+            choices.push(LevelUpChoice::Learn(perk));
+        }
+    }
+    choices
+}
+
+/// Spends one pick, returning whether any are left
+pub fn apply(ecs : &mut World, choice : LevelUpChoice) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut experience = ecs.write_storage::<Experience>();
+    let mut stats = ecs.write_storage::<CombatStats>();
+    let mut perks = ecs.write_storage::<Perks>();
+    let stats = stats.get_mut(player_entity).unwrap();
+    match choice {
+        LevelUpChoice::Vitality => {
+            stats.max_hp += 8;
+            stats.hp += 8;
+        }
+        LevelUpChoice::Might => stats.power += 1,
+        LevelUpChoice::Guard => stats.defense += 1,
+        LevelUpChoice::Learn(perk) => {
+            if let Some(perks) = perks.get_mut(player_entity) {
+                perks.list.push(perk);
+            }
+        }
+    }
+
+    let exp = experience.get_mut(player_entity).unwrap();
+    exp.picks -= 1;
+    ecs.write_resource::<Gamelog>().entries.push(choice.describe().to_string());
+    exp.picks > 0
+}
\ No newline at end of file
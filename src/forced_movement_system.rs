@@ -0,0 +1,90 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator, RGB};
+use super::{Map, TileType, Position, Fov, BlocksTile, Name, ForcedMove, SufferDamage, DamageType, gamelog::Gamelog,
+    particle_system::ParticleBuilder};
+
+/// Dice rolled for each body slammed into something
+const COLLISION_DIE : i32 = 4;
+
+pub struct ForcedMovementSystem {}
+
+impl<'a> System<'a> for ForcedMovementSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, Map>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Point>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteStorage<'a, ForcedMove>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, Fov>,
+                        ReadStorage<'a, BlocksTile>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SufferDamage>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut map, player_entity, mut player_pos, mut log, mut rng, mut particles, mut forced_moves,
+            mut positions, mut fovs, blockers, names, mut inflict_damage) = data;
+
+        let moves : Vec<(Entity, ForcedMove)> = (&entities, &forced_moves).join()
+            .map(|(entity, forced)| (entity, forced.clone()))
+            .collect();
+        forced_moves.clear();
+
+        for (entity, forced) in moves {
+            let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+            let blocks = blockers.get(entity).is_some();
+
+            for _step in 0..forced.distance {
+                let Some(pos) = positions.get(entity) else {break};
+                let (x, y) = (pos.x, pos.y);
+                let (nx, ny) = (x + forced.dx, y + forced.dy);
+                let seen = map.visible_tiles[map.xy_idx(x, y)];
+
+                let out_of_bounds = nx < 0 || nx >= map.width || ny < 0 || ny >= map.height;
+                if out_of_bounds || map.tiles[map.xy_idx(nx, ny)] == TileType::Wall {
+                    let damage = rng.roll_dice(1, COLLISION_DIE);
+                    SufferDamage::new_damage(&mut inflict_damage, entity, damage, DamageType::Physical,
+                        forced.source, "slammed into a wall");
+                    if seen {
+                        log.entries.push(format!("{} slams into the wall, for {} hp", name, damage));
+                        particles.flash(x, y, RGB::named(rltk::ORANGE));
+                    }
+                    break;
+                }
+
+                let dest = map.xy_idx(nx, ny);
+                if map.blocked[dest] {
+                    if let Some(other) = map.tile_content[dest].iter().find(|other| blockers.get(**other).is_some()) {
+                        let other_name = names.get(*other).map_or("something", |n| n.name.as_str());
+                        let damage = rng.roll_dice(1, COLLISION_DIE);
+                        SufferDamage::new_damage(&mut inflict_damage, entity, damage, DamageType::Physical,
+                            forced.source, "a collision");
+                        SufferDamage::new_damage(&mut inflict_damage, *other, damage, DamageType::Physical,
+                            forced.source, "a collision");
+                        if seen {
+                            log.entries.push(format!("{} crashes into {}, for {} hp each", name, other_name, damage));
+                            particles.flash(nx, ny, RGB::named(rltk::ORANGE));
+                        }
+                    }
+                    break;
+                }
+
+                let from = map.xy_idx(x, y);
+                map.move_entity(entity, from, dest, blocks);
+                if let Some(pos) = positions.get_mut(entity) {
+                    pos.x = nx;
+                    pos.y = ny;
+                }
+                if let Some(fov) = fovs.get_mut(entity) {
+                    fov.dirty = true;
+                }
+                if entity == *player_entity {
+                    *player_pos = Point::new(nx, ny);
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -1,34 +1,50 @@
 use specs::prelude::*;
+use specs::hibitset::BitSetLike;
 use super::{Fov, Position, Map, Player};
 use rltk::{field_of_view, Point};
 
+/// Monsters further than this from the player are left dormant
+pub const ACTIVE_RADIUS : f32 = 24.0;
+
 pub struct FovSystem {}
 
 impl<'a> System<'a> for FovSystem {
     type SystemData = ( WriteExpect<'a, Map>,
+                        ReadExpect<'a, Point>,
                         Entities<'a>,
                         WriteStorage<'a, Fov>,
                         WriteStorage<'a, Position>,
                         ReadStorage<'a, Player>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, entities, mut fov, pos, player) = data;
+        let (mut map, player_pos, entities, mut fov, pos, player) = data;
 
         for (ent, fov, pos) in (&entities, &mut fov, &pos).join() {
             if fov.dirty {
+                let is_player = player.get(ent).is_some();
+
+                // Far away monsters can't see the player, so skip them until they get closer
+                if !is_player && rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos) > ACTIVE_RADIUS {
+                    if !fov.visible.is_empty() {
+                        fov.visible.clear();
+                    }
+                    continue;
+                }
+
                 fov.dirty = false;
-                fov.visible_tiles.clear();
-                fov.visible_tiles = field_of_view(Point::new(pos.x, pos.y), fov.range, &*map);
-                fov.visible_tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height);
+                fov.visible.clear();
+                for vis in field_of_view(Point::new(pos.x, pos.y), fov.range, &*map).iter() {
+                    if vis.x >= 0 && vis.x < map.width && vis.y >= 0 && vis.y < map.height {
+                        fov.visible.add(map.xy_idx(vis.x, vis.y) as u32);
+                    }
+                }
 
                 // Reveals what player can see
-                let _p : Option<&Player> = player.get(ent);
-                if let Some(_p) = _p {
+                if is_player {
                     for t in map.visible_tiles.iter_mut() {*t = false};
-                    for vis in fov.visible_tiles.iter() {
-                        let idx = map.xy_idx(vis.x, vis.y);
-                        map.revealed_tiles[idx] = true;
-                        map.visible_tiles[idx] = true;
+                    for idx in (&fov.visible).iter() {
+                        map.revealed_tiles[idx as usize] = true;
+                        map.visible_tiles[idx as usize] = true;
                     }
                 }
             }
@@ -1,3 +1,11 @@
 pub struct Gamelog {
     pub entries : Vec<String>
+}
+
+/// Tallies for the current run, shown when it ends
+#[derive(Default)]
+pub struct RunStats {
+    pub kills : i32,
+    pub turns : i32,
+    pub cause_of_death : Option<String>
 }
\ No newline at end of file
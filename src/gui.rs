@@ -1,6 +1,9 @@
-use rltk::{RGB, Rltk, VirtualKeyCode};
+use rltk::{RGB, Rltk, VirtualKeyCode, Point};
 use specs::prelude::*;
-use super::{CombatStats, Player, gamelog::Gamelog, InBackpack, Name, State, RunState, Map};
+use specs::hibitset::BitSetLike;
+use super::{CombatStats, Player, gamelog::{Gamelog, RunStats}, InBackpack, Name, State, RunState, Map, Position, Awareness,
+    AwarenessState, Experience, HungerClock, HungerState, experience::{self, xp_to_next, LevelUpChoice},
+    identification::ItemNames, Stack, Encumbrance, Fov, Wand, Item, InContainer, Purse, shop, CurseStatus, Blessing};
 
 
 #[derive(PartialEq, Copy, Clone)]
@@ -24,59 +27,161 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         let health = format!("HP: {} / {}", stats.hp, stats.max_hp);
         ctx.print_color(12, 43, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &health);
 
-        ctx.draw_bar_horizontal(28, 43, 51, stats.hp, stats.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+        ctx.draw_bar_horizontal(46, 43, 22, stats.hp, stats.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    }
+
+    // Hunger, only shown when it is worth noticing
+    let hunger = ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &hunger).join() {
+        match clock.state {
+            HungerState::WellFed => ctx.print_color(70, 43, RGB::named(rltk::GREEN), RGB::named(rltk::BLACK), "Well Fed"),
+            HungerState::Normal => {}
+            HungerState::Hungry => ctx.print_color(70, 43, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), "Hungry"),
+            HungerState::Starving => ctx.print_color(70, 43, RGB::named(rltk::RED), RGB::named(rltk::BLACK), "Starving")
+        }
+    }
+
+    // Carrying too much
+    let encumbrance = ecs.read_storage::<Encumbrance>();
+    for (_player, load) in (&players, &encumbrance).join() {
+        if load.overburdened() {
+            ctx.print_color(68, 49, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), "Overburdened");
+        }
+    }
+
+    // Gold carried
+    let purses = ecs.read_storage::<Purse>();
+    for (_player, purse) in (&players, &purses).join() {
+        ctx.print_color(2, 49, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), format!("Gold: {}", purse.gold));
+    }
+
+    // Level and progress toward the next one
+    let experience = ecs.read_storage::<Experience>();
+    for (_player, exp) in (&players, &experience).join() {
+        let level = format!("Lv {} XP {}/{}", exp.level, exp.xp, xp_to_next(exp.level));
+        ctx.print_color(25, 43, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), &level);
     }
     
     let log = ecs.fetch::<Gamelog>();
     
     // Console logs
-    let mut y = 44;
-    for s in log.entries.iter().rev() {
-        if y < 49 {ctx.print(2, y, s);}
-        y += 1;
+    for (y, s) in (44..49).zip(log.entries.iter().rev()) {
+        ctx.print(2, y, s);
     }
+
+    draw_tooltips(ecs, ctx);
+}
+
+/// Names whatever is under the mouse, and how aware monsters there are of the player
+fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let names = ecs.read_storage::<Name>();
+    let positions = ecs.read_storage::<Position>();
+    let awareness = ecs.read_storage::<Awareness>();
+    let item_names = ecs.fetch::<ItemNames>();
+
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    if mouse_x < 0 || mouse_x >= map.width || mouse_y < 0 || mouse_y >= map.height {return;}
+    if !map.visible_tiles[map.xy_idx(mouse_x, mouse_y)] {return;}
+
+    let mut tooltip : Vec<String> = Vec::new();
+    for (name, pos, aware) in (&names, &positions, awareness.maybe()).join() {
+        if pos.x == mouse_x && pos.y == mouse_y {
+            let state = match aware.map(|aware| aware.state) {
+                Some(AwarenessState::Asleep) => " (asleep)",
+                Some(AwarenessState::Unaware) => " (unaware)",
+                Some(AwarenessState::Aware) => " (alert)",
+                None => ""
+            };
+            tooltip.push(format!("{}{}", item_names.display(&name.name), state));
+        }
+    }
+    if tooltip.is_empty() {return;}
+
+    // Keep the box on screen by flipping it to the left half of the map
+    let width = tooltip.iter().map(|line| line.len()).max().unwrap() as i32;
+    let x = if mouse_x > 40 {mouse_x - width - 1} else {mouse_x + 1};
+    for (y, line) in (mouse_y..).zip(tooltip.iter()) {
+        ctx.print_color(x, y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), line);
+    }
+    ctx.set_bg(mouse_x, mouse_y, RGB::named(rltk::MAGENTA));
 }
 
 #[derive(PartialEq, Copy, Clone)]
-pub enum ItemMenuResult {Cancel, NoResponse, Selected}
+pub enum ItemMenuResult {Cancel, NoResponse, Selected, Page(usize)}
 
-/// Inventory Menu
-pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
-    let player_entity = gs.ecs.fetch::<Entity>();
-    let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<InBackpack>();
-    let entities = gs.ecs.entities();
+/// Letters can only address this many items at once, so longer lists are paged
+const ITEMS_PER_PAGE : usize = 26;
 
-    let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity);
-    let count = inventory.count();
+/// The items `held` picks out, named the way the player knows them, with stacks counted and known curses shown
+fn labelled_items(ecs: &World, held: impl Fn(Entity) -> bool) -> Vec<(Entity, String)> {
+    let names = ecs.read_storage::<Name>();
+    let items = ecs.read_storage::<Item>();
+    let stacks = ecs.read_storage::<Stack>();
+    let wands = ecs.read_storage::<Wand>();
+    let curses = ecs.read_storage::<CurseStatus>();
+    let entities = ecs.entities();
+    let item_names = ecs.fetch::<ItemNames>();
 
-    let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
-    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Inventory");
-    ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Esc to close");
+    (&entities, &items, &names, stacks.maybe(), wands.maybe()).join()
+        .filter(|(entity, ..)| held(*entity))
+        .map(|(entity, _item, name, stack, wand)| {
+            let label = match (stack, wand) {
+                (Some(stack), _) if stack.count > 1 => format!("{} (x{})", item_names.display(&name.name), stack.count),
+                (_, Some(wand)) if wand.charges > 0 => format!("{} ({} charges)", item_names.display(&name.name), wand.charges),
+                (_, Some(_wand)) => format!("{} (empty)", item_names.display(&name.name)),
+                _ => item_names.display(&name.name)
+            };
+            let label = match curses.get(entity).filter(|curse| curse.known).map(|curse| curse.blessing) {
+                Some(Blessing::Cursed) => format!("{} (cursed)", label),
+                Some(Blessing::Blessed) => format!("{} (blessed)", label),
+                None => label
+            };
+            (entity, label)
+        })
+        .collect()
+}
 
-    let mut equippable : Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity) {
+/// What the player carries
+fn carried_items(ecs: &World) -> Vec<(Entity, String)> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    labelled_items(ecs, |item| backpack.get(item).is_some_and(|pack| pack.owner == player_entity))
+}
+
+/// One page of items to pick from, flipped with the left and right arrows
+fn item_menu(ctx: &mut Rltk, title: &str, items: &[(Entity, String)], page: usize) -> (ItemMenuResult, Option<Entity>) {
+    let pages = items.len().div_ceil(ITEMS_PER_PAGE).max(1);
+    let page = page.min(pages - 1);
+    let shown = &items[page * ITEMS_PER_PAGE..items.len().min((page + 1) * ITEMS_PER_PAGE)];
+    let count = shown.len();
+
+    let y = (25 - (count / 2)) as i32;
+    ctx.draw_box(15, y-2, 45, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    let footer = if pages > 1 {format!("Esc to close, arrows: page {}/{}", page + 1, pages)} else {"Esc to close".to_string()};
+    ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), footer);
+
+    for (j, (_entity, label)) in shown.iter().enumerate() {
+        let y = y + j as i32;
         ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
-        y += 1;
-        j += 1;
+        ctx.print(21, y, label);
     }
 
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => {
             match key {
-                VirtualKeyCode::Escape => {(ItemMenuResult::Cancel, None)}
+                VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+                VirtualKeyCode::Left if page > 0 => (ItemMenuResult::Page(page - 1), None),
+                VirtualKeyCode::Right if page + 1 < pages => (ItemMenuResult::Page(page + 1), None),
                 _ => {
                     let selection = rltk::letter_to_option(key);
                     if selection > -1 && selection < count as i32 {
-                        return (ItemMenuResult::Selected, Some(equippable[selection as usize]));
+                        return (ItemMenuResult::Selected, Some(shown[selection as usize].0));
                     }
                     (ItemMenuResult::NoResponse, None)
                 }
@@ -85,51 +190,174 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     }
 }
 
+/// Inventory Menu, headed by how much the player carries against what they can
+pub fn show_inventory(gs: &mut State, ctx: &mut Rltk, page: usize) -> (ItemMenuResult, Option<Entity>) {
+    let title = {
+        let player_entity = gs.ecs.fetch::<Entity>();
+        let encumbrance = gs.ecs.read_storage::<Encumbrance>();
+        match encumbrance.get(*player_entity) {
+            Some(load) => format!("Inventory ({:.1}/{:.0} lb)", load.carried, load.capacity),
+            None => "Inventory".to_string()
+        }
+    };
+    item_menu(ctx, &title, &carried_items(&gs.ecs), page)
+}
+
 /// Item Menu for dropping items in backpack
-pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk, page: usize) -> (ItemMenuResult, Option<Entity>) {
+    item_menu(ctx, "Drop Which Item?", &carried_items(&gs.ecs), page)
+}
+
+/// What lies inside an opened container, picking one takes it
+pub fn show_container(gs : &mut State, ctx : &mut Rltk, container : Entity, page: usize) -> (ItemMenuResult, Option<Entity>) {
+    let title = match gs.ecs.read_storage::<Name>().get(container) {
+        Some(name) => format!("Take from the {}", name.name),
+        None => "Take Which Item?".to_string()
+    };
+    let inside = gs.ecs.read_storage::<InContainer>();
+    let items = labelled_items(&gs.ecs, |item| inside.get(item).is_some_and(|held| held.container == container));
+    item_menu(ctx, &title, &items, page)
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ShopResult {Leave, NoResponse, Page(usize), SwitchMode, Trade(Entity)}
+
+/// A merchant's wares with what they cost, or the player's items with what they fetch. Tab flips between the two.
+pub fn show_shop(gs : &mut State, ctx : &mut Rltk, merchant : Entity, selling : bool, page : usize) -> ShopResult {
+    if ctx.key == Some(VirtualKeyCode::Tab) {
+        return ShopResult::SwitchMode;
+    }
+
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let gold = gs.ecs.read_storage::<Purse>().get(player_entity).map_or(0, |purse| purse.gold);
+    let name = gs.ecs.read_storage::<Name>().get(merchant).map_or("Merchant".to_string(), |n| n.name.clone());
+    let (title, owner) = if selling {
+        (format!("Sell to {} ({} gold, Tab: buy)", name, gold), player_entity)
+    } else {
+        (format!("Buy from {} ({} gold, Tab: sell)", name, gold), merchant)
+    };
+
+    let items : Vec<(Entity, String)> = {
+        let backpack = gs.ecs.read_storage::<InBackpack>();
+        labelled_items(&gs.ecs, |item| backpack.get(item).is_some_and(|pack| pack.owner == owner))
+    };
+    let priced : Vec<(Entity, String)> = items.into_iter()
+        .map(|(item, label)| {
+            let price = if selling {shop::sell_price(&gs.ecs, item)} else {shop::buy_price(&gs.ecs, item)};
+            (item, format!("{} - {} gold", label, price))
+        })
+        .collect();
+
+    match item_menu(ctx, &title, &priced, page) {
+        (ItemMenuResult::Cancel, _) => ShopResult::Leave,
+        (ItemMenuResult::Page(page), _) => ShopResult::Page(page),
+        (ItemMenuResult::Selected, Some(item)) => ShopResult::Trade(item),
+        _ => ShopResult::NoResponse
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ConfirmResult {NoResponse, Yes, No}
+
+/// Makes sure the player means to attack someone who would rather trade
+pub fn confirm_attack(gs : &mut State, ctx : &mut Rltk, target : Entity) -> ConfirmResult {
+    let name = gs.ecs.read_storage::<Name>().get(target).map_or("them".to_string(), |n| n.name.clone());
+    let question = format!("Really attack {}? (y/n)", name);
+    ctx.draw_box(15, 23, 45, 2, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, 24, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), question);
+
+    match ctx.key {
+        Some(VirtualKeyCode::Y) => ConfirmResult::Yes,
+        Some(VirtualKeyCode::N) | Some(VirtualKeyCode::Escape) => ConfirmResult::No,
+        _ => ConfirmResult::NoResponse
+    }
+}
+
+/// Shades the tiles in sight and range of the player; clicking one of them picks it as the target
+pub fn ranged_target(gs : &mut State, ctx : &mut Rltk, range : i32) -> (ItemMenuResult, Option<Point>) {
     let player_entity = gs.ecs.fetch::<Entity>();
-    let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<InBackpack>();
-    let entities = gs.ecs.entities();
+    let player_pos = gs.ecs.fetch::<Point>();
+    let fovs = gs.ecs.read_storage::<Fov>();
+    let width = gs.ecs.fetch::<Map>().width;
 
-    let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity );
-    let count = inventory.count();
+    ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Select a target, Esc to cancel");
 
-    let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
-    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Drop Which Item?");
-    ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Esc to close");
+    let in_range : Vec<Point> = match fovs.get(*player_entity) {
+        None => return (ItemMenuResult::Cancel, None),
+        Some(fov) => (&fov.visible).iter()
+            .map(|idx| Point::new(idx as i32 % width, idx as i32 / width))
+            .filter(|tile| rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *tile) <= range as f32)
+            .collect()
+    };
+    for tile in in_range.iter() {
+        ctx.set_bg(tile.x, tile.y, RGB::named(rltk::BLUE));
+    }
+
+    let mouse = ctx.mouse_point();
+    let valid = in_range.contains(&mouse);
+    ctx.set_bg(mouse.x, mouse.y, if valid {RGB::named(rltk::CYAN)} else {RGB::named(rltk::RED)});
 
-    let mut equippable : Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity ) {
+    if ctx.key == Some(VirtualKeyCode::Escape) {
+        return (ItemMenuResult::Cancel, None);
+    }
+    if ctx.left_click && valid {
+        return (ItemMenuResult::Selected, Some(mouse));
+    }
+    (ItemMenuResult::NoResponse, None)
+}
+
+/// Level up screen, picking one stat or perk per level gained
+pub fn level_up_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<LevelUpChoice>) {
+    let choices = experience::choices(&gs.ecs);
+    let count = choices.len();
+
+    let y = (25 - (count / 2)) as i32;
+    ctx.draw_box(15, y-2, 45, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Level Up! Choose one");
+
+    for (j, choice) in choices.iter().enumerate() {
+        let y = y + j as i32;
         ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
-        y += 1;
-        j += 1;
+        ctx.print(21, y, choice.describe());
     }
 
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => {
-            match key {
-                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
-                _ => { 
-                    let selection = rltk::letter_to_option(key);
-                    if selection > -1 && selection < count as i32 {
-                        return (ItemMenuResult::Selected, Some(equippable[selection as usize]));
-                    }  
-                    (ItemMenuResult::NoResponse, None)
-                }
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count as i32 {
+                return (ItemMenuResult::Selected, Some(choices[selection as usize]));
             }
+            (ItemMenuResult::NoResponse, None)
         }
     }
 }
 
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult {NoSelection, QuitToMenu}
+
+/// Death screen summing up the run
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    let stats = ecs.fetch::<RunStats>();
+    let depth = ecs.fetch::<Map>().depth;
+    let cause = stats.cause_of_death.clone().unwrap_or_else(|| "Died".to_string());
+
+    ctx.print_color_centered(15, RGB::named(rltk::RED), RGB::named(rltk::BLACK), "Your journey has ended!");
+    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &cause);
+    ctx.print_color_centered(20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("Depth reached: {}", depth));
+    ctx.print_color_centered(21, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("Turns taken: {}", stats.turns));
+    ctx.print_color_centered(22, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("Monsters slain: {}", stats.kills));
+    ctx.print_color_centered(25, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to the menu.");
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::QuitToMenu
+    }
+}
+
 /// Main Game Menu
 pub fn menu(gs:&mut State, ctx: &mut Rltk) -> MenuResult {
     let runstate = gs.ecs.fetch::<RunState>();
@@ -155,19 +383,17 @@ pub fn menu(gs:&mut State, ctx: &mut Rltk) -> MenuResult {
                 match key {
                     VirtualKeyCode::Escape => {return MenuResult::NoSelection{selected:MenuSelection::Quit}}
                     VirtualKeyCode::Up => {
-                        let newchoice;
-                        match select {
-                            MenuSelection::NewGame => newchoice = MenuSelection::Quit,
-                            MenuSelection::Quit => newchoice = MenuSelection::NewGame
-                        }
+                        let newchoice = match select {
+                            MenuSelection::NewGame => MenuSelection::Quit,
+                            MenuSelection::Quit => MenuSelection::NewGame
+                        };
                         return MenuResult::NoSelection{selected:newchoice}
                     }
                     VirtualKeyCode::Down => {
-                        let newchoice;
-                        match select {
-                            MenuSelection::NewGame => newchoice = MenuSelection::Quit,
-                            MenuSelection::Quit => newchoice = MenuSelection::NewGame
-                        }
+                        let newchoice = match select {
+                            MenuSelection::NewGame => MenuSelection::Quit,
+                            MenuSelection::Quit => MenuSelection::NewGame
+                        };
                         return MenuResult::NoSelection{selected:newchoice}
                     }
                     VirtualKeyCode::Return => return MenuResult::Selection{selected:select},
@@ -0,0 +1,54 @@
+use specs::prelude::*;
+use super::{HungerClock, HungerState, RunState, SufferDamage, DamageType, gamelog::Gamelog};
+
+/// Turns spent in each state before sliding to the next
+pub const WELL_FED_TURNS : i32 = 20;
+const NORMAL_TURNS : i32 = 300;
+const HUNGRY_TURNS : i32 = 200;
+
+/// Advances every hunger clock by one player turn, starving whoever has gone too long without food
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteStorage<'a, HungerClock>,
+                        WriteStorage<'a, SufferDamage>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, runstate, mut log, mut hunger_clocks, mut inflict_damage) = data;
+
+        if *runstate != RunState::PlayerTurn {return;}
+
+        for (entity, clock) in (&entities, &mut hunger_clocks).join() {
+            clock.duration -= 1;
+            if clock.duration > 0 {continue;}
+
+            let is_player = entity == *player_entity;
+            match clock.state {
+                HungerState::WellFed => {
+                    clock.state = HungerState::Normal;
+                    clock.duration = NORMAL_TURNS;
+                    if is_player {log.entries.push("You are no longer well fed.".to_string());}
+                }
+                HungerState::Normal => {
+                    clock.state = HungerState::Hungry;
+                    clock.duration = HUNGRY_TURNS;
+                    if is_player {log.entries.push("You are hungry.".to_string());}
+                }
+                HungerState::Hungry => {
+                    clock.state = HungerState::Starving;
+                    clock.duration = 0;
+                    if is_player {log.entries.push("You are starving!".to_string());}
+                }
+                HungerState::Starving => {
+                    SufferDamage::new_damage(&mut inflict_damage, entity, 1, DamageType::Physical, None, "starvation");
+                    if is_player {log.entries.push("Your hunger pangs are getting painful! You take 1 hp damage.".to_string());}
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, HashSet};
+use rltk::RandomNumberGenerator;
+use serde::Deserialize;
+use super::raws;
+
+const POTION_LOOKS : [&str; 8] = ["murky", "bubbling", "fizzy", "cloudy", "glowing", "syrupy", "smoky", "speckled"];
+const POTION_COLOURS : [&str; 10] = ["red", "blue", "green", "amber", "violet", "black", "silver", "golden", "pink", "grey"];
+const WAND_LOOKS : [&str; 6] = ["twisted", "carved", "polished", "knobbly", "slender", "cracked"];
+const WAND_MATERIALS : [&str; 9] = ["oak", "bone", "crystal", "iron", "ebony", "copper", "glass", "willow", "jade"];
+const SCROLL_SYLLABLES : [&str; 16] = ["xu", "zor", "bel", "ka", "mi", "tor", "ven", "ul", "ash", "nex", "ro", "qua", "fen", "dra", "ib", "sol"];
+
+/// Kinds of consumable that hide what they are until used
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemClass {
+    Potion,
+    Scroll,
+    Wand
+}
+
+/// How many distinct appearances there are for a class. The raws may not list more unidentified items
+/// of a class than this, or handing out appearances would never finish.
+pub fn look_count(class : ItemClass) -> usize {
+    match class {
+        ItemClass::Potion => POTION_LOOKS.len() * POTION_COLOURS.len(),
+        ItemClass::Wand => WAND_LOOKS.len() * WAND_MATERIALS.len(),
+        ItemClass::Scroll => {
+            // Two or three words of two or three syllables each
+            let words = SCROLL_SYLLABLES.len().pow(2) + SCROLL_SYLLABLES.len().pow(3);
+            words.saturating_pow(2).saturating_add(words.saturating_pow(3))
+        }
+    }
+}
+
+/// What unidentified items look like this run, and which of them the player has learned
+#[derive(Default)]
+pub struct ItemNames {
+    appearances : HashMap<String, (ItemClass, String)>,
+    identified : HashSet<String>
+}
+
+impl ItemNames {
+    /// Hands every unidentified item in the raws a distinct appearance drawn from the run seed
+    pub fn new(seed : u64) -> ItemNames {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let mut names = ItemNames::default();
+        let mut used = HashSet::new();
+
+        for (real, class) in raws::unidentified_items() {
+            let look = loop {
+                let look = match class {
+                    ItemClass::Potion => potion_look(&mut rng),
+                    ItemClass::Scroll => scroll_look(&mut rng),
+                    ItemClass::Wand => wand_look(&mut rng)
+                };
+                if used.insert(look.clone()) {break look;}
+            };
+            names.appearances.insert(real.to_string(), (class, look));
+        }
+        names
+    }
+
+    /// The name the player knows an item by
+    pub fn display(&self, real : &str) -> String {
+        match self.appearances.get(real) {
+            Some((_class, look)) if !self.identified.contains(real) => look.clone(),
+            _ => real.to_string()
+        }
+    }
+
+    /// The class of an item the player has not identified yet, which is all they know about it
+    pub fn hidden_class(&self, real : &str) -> Option<ItemClass> {
+        self.appearances.get(real).filter(|_| !self.identified.contains(real)).map(|(class, _look)| *class)
+    }
+
+    /// Learns an item's real name for the rest of the run. True if it was not known before.
+    pub fn identify(&mut self, real : &str) -> bool {
+        self.appearances.contains_key(real) && self.identified.insert(real.to_string())
+    }
+}
+
+fn potion_look(rng : &mut RandomNumberGenerator) -> String {
+    let look = rng.random_slice_entry(&POTION_LOOKS).unwrap();
+    let colour = rng.random_slice_entry(&POTION_COLOURS).unwrap();
+    format!("{} {} potion", look, colour)
+}
+
+fn wand_look(rng : &mut RandomNumberGenerator) -> String {
+    let look = rng.random_slice_entry(&WAND_LOOKS).unwrap();
+    let material = rng.random_slice_entry(&WAND_MATERIALS).unwrap();
+    format!("{} {} wand", look, material)
+}
+
+fn scroll_look(rng : &mut RandomNumberGenerator) -> String {
+    let words : Vec<String> = (0..rng.roll_dice(1, 2) + 1)
+        .map(|_| (0..rng.roll_dice(1, 2) + 1).map(|_| *rng.random_slice_entry(&SCROLL_SYLLABLES).unwrap()).collect::<String>())
+        .collect();
+    format!("scroll titled \"{}\"", words.join(" ").to_uppercase())
+}
\ No newline at end of file
@@ -1,27 +1,78 @@
 use specs::prelude::*;
 use super::{WantsToPickupItem, Name, InBackpack, Position, gamelog::Gamelog, CombatStats,
-    Potion, WantsToDrinkPotion, WantsToDropItem, Equipped, Equippable};
+    Potion, WantsToDrinkPotion, WantsToDropItem, Equipped, Equippable, Map, ProvidesFood, HungerClock, HungerState,
+    hunger_system::WELL_FED_TURNS, identification::ItemNames, Stack, InContainer, Gold, Purse, CurseStatus, Blessing};
+
+/// Whether the player can see what an entity is doing
+pub fn witnessed(map : &Map, pos : Option<&Position>) -> bool {
+    pos.is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+}
+
+/// Uses up one of an item, deleting it once the last of its stack is gone
+pub fn use_up(entities : &Entities, stacks : &mut WriteStorage<Stack>, item : Entity) {
+    if let Some(stack) = stacks.get_mut(item) {
+        if stack.count > 1 {
+            stack.count -= 1;
+            return;
+        }
+    }
+    entities.delete(item).expect("Delete item failed");
+}
+
+/// An item's name with its stack size when there is more than one
+fn counted(name : String, stack : Option<&Stack>) -> String {
+    match stack {
+        Some(stack) if stack.count > 1 => format!("{} (x{})", name, stack.count),
+        _ => name
+    }
+}
 
 pub struct ItemCollectionSystem {}
 
 impl<'a> System<'a> for ItemCollectionSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Map>,
                         WriteExpect<'a, Gamelog>,
+                        ReadExpect<'a, ItemNames>,
+                        Entities<'a>,
                         WriteStorage<'a, WantsToPickupItem>,
                         WriteStorage<'a, Position>,
                         ReadStorage<'a, Name>,
-                        WriteStorage<'a, InBackpack>);
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Stack>,
+                        WriteStorage<'a, InContainer>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) = data;
+        let (player_entity, map, mut gamelog, item_names, entities, mut wants_pickup, mut positions, names, mut backpack, mut stacks,
+            mut in_container) = data;
 
         for pickup in wants_pickup.join() {
+            let picked_name = counted(item_names.display(&names.get(pickup.item).unwrap().name), stacks.get(pickup.item));
             positions.remove(pickup.item);
-            backpack.insert(pickup.item, InBackpack{owner: pickup.collected_by}).expect("Unable to place item in backpack");
+            in_container.remove(pickup.item);
+
+            // A stack joins one of the same kind already carried
+            let carried_stack = stacks.get(pickup.item).and_then(|picked| {
+                let name = &names.get(pickup.item).unwrap().name;
+                (&entities, &backpack, &names, &stacks).join()
+                    .find(|(item, pack, other, _stack)| *item != pickup.item && pack.owner == pickup.collected_by && other.name == *name)
+                    .map(|(item, ..)| (item, picked.count))
+            });
+            match carried_stack {
+                Some((stack, count)) => {
+                    stacks.get_mut(stack).unwrap().count += count;
+                    entities.delete(pickup.item).expect("Unable to merge stack");
+                }
+                None => {
+                    backpack.insert(pickup.item, InBackpack{owner: pickup.collected_by}).expect("Unable to place item in backpack");
+                }
+            }
 
             if pickup.collected_by == *player_entity {
-                gamelog.entries.push(format!("{} has been picked up", names.get(pickup.item).unwrap().name));
+                gamelog.entries.push(format!("{} has been picked up", picked_name));
+            } else if witnessed(&map, positions.get(pickup.collected_by)) {
+                gamelog.entries.push(format!("{} picks up the {}", names.get(pickup.collected_by).unwrap().name, picked_name));
             }
         }
 
@@ -29,12 +80,40 @@ impl<'a> System<'a> for ItemCollectionSystem {
     }
 }
 
+/// Anyone with a purse scoops up the gold they step on
+pub struct GoldCollectionSystem {}
+
+impl<'a> System<'a> for GoldCollectionSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Gamelog>,
+                        Entities<'a>,
+                        ReadStorage<'a, Gold>,
+                        ReadStorage<'a, Position>,
+                        WriteStorage<'a, Purse>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut gamelog, entities, gold, positions, mut purses) = data;
+
+        for (entity, purse, pos) in (&entities, &mut purses, &positions).join() {
+            for (pile, coins, _pile_pos) in (&entities, &gold, &positions).join().filter(|(_pile, _coins, at)| at.x == pos.x && at.y == pos.y) {
+                purse.gold += coins.amount;
+                entities.delete(pile).expect("Unable to delete gold");
+                if entity == *player_entity {
+                    gamelog.entries.push(format!("You pick up {} gold.", coins.amount));
+                }
+            }
+        }
+    }
+}
+
 pub struct PotionUseSystem {}
 
 impl<'a> System<'a> for PotionUseSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Map>,
                         WriteExpect<'a, Gamelog>,
+                        WriteExpect<'a, ItemNames>,
                         Entities<'a>,
                         WriteStorage<'a, WantsToDrinkPotion>,
                         ReadStorage<'a, Name>,
@@ -42,10 +121,16 @@ impl<'a> System<'a> for PotionUseSystem {
                         WriteStorage<'a, CombatStats>,
                         ReadStorage<'a, Equippable>,
                         WriteStorage<'a, Equipped>,
-                        WriteStorage<'a, InBackpack>);
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, ProvidesFood>,
+                        WriteStorage<'a, HungerClock>,
+                        WriteStorage<'a, Stack>,
+                        WriteStorage<'a, CurseStatus>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (player_entity, mut gamelog, entities, mut want_potion, names, potions, mut combat_stats, equippable, mut equip, mut backpack) = data;
+        let (player_entity, map, mut gamelog, mut item_names, entities, mut want_potion, names, potions, mut combat_stats, equippable, mut equip, mut backpack, positions,
+            food, mut hunger_clocks, mut stacks, mut curses) = data;
 
         for (entity, drink, stats) in (&entities, &want_potion, &mut combat_stats).join() {
             let potion = potions.get(drink.potion);
@@ -53,24 +138,56 @@ impl<'a> System<'a> for PotionUseSystem {
                 None => {}
                 Some(potion) => {
                     stats.hp = i32::min(stats.max_hp, stats.hp + potion.heal_amount);
+                    let real_name = &names.get(drink.potion).unwrap().name;
                     if entity == *player_entity {
-                        gamelog.entries.push(format!("You drink the {}, healing {} hp", names.get(drink.potion).unwrap().name, potion.heal_amount));
+                        let known_as = item_names.display(real_name);
+                        gamelog.entries.push(format!("You drink the {}, healing {} hp", known_as, potion.heal_amount));
+                        // Drinking it is the surest way to find out what it was
+                        if item_names.identify(real_name) {
+                            gamelog.entries.push(format!("The {} was a {}!", known_as, real_name));
+                        }
+                    } else if witnessed(&map, positions.get(entity)) {
+                        gamelog.entries.push(format!("{} drinks a {}", names.get(entity).unwrap().name, item_names.display(real_name)));
                     }
-                    entities.delete(drink.potion).expect("Delete potion failed");
+                    use_up(&entities, &mut stacks, drink.potion);
                 }
             }
             
+            if food.get(drink.potion).is_some() {
+                if let Some(clock) = hunger_clocks.get_mut(entity) {
+                    clock.state = HungerState::WellFed;
+                    clock.duration = WELL_FED_TURNS;
+                }
+                if entity == *player_entity {
+                    gamelog.entries.push(format!("You eat the {}.", names.get(drink.potion).unwrap().name));
+                }
+                use_up(&entities, &mut stacks, drink.potion);
+            }
+
             // Pretty raw way to equip things while they are still called potions. Gonna fix
             let equip_item = equippable.get(drink.potion);
             match equip_item {
                 None => {}
                 Some(can_equip) => {
                     let target_slot = can_equip.slot;
-                    
+
+                    // Cursed gear already in the slot will not come off
+                    let stuck = (&entities, &equip).join()
+                        .find(|(item, worn)| worn.owner == entity && worn.slot == target_slot &&
+                            curses.get(*item).is_some_and(|curse| curse.blessing == Blessing::Cursed))
+                        .map(|(item, _worn)| item);
+                    if let Some(stuck) = stuck {
+                        if entity == *player_entity {
+                            curses.get_mut(stuck).unwrap().known = true;
+                            gamelog.entries.push(format!("You cannot remove the {}, it is cursed!", names.get(stuck).unwrap().name));
+                        }
+                        continue;
+                    }
+
                     // Remove any item in equipment slot first before trying to insert a new one
                     let mut to_unequip : Vec<Entity> = Vec::new();
                     for(item, already_equipped, name) in (&entities, &equip, &names).join() {
-                        if already_equipped.owner == *player_entity && already_equipped.slot == target_slot {
+                        if already_equipped.owner == entity && already_equipped.slot == target_slot {
                             to_unequip.push(item);
                             if entity == *player_entity {
                                 gamelog.entries.push(format!("{} has been unequipped.", name.name));
@@ -86,7 +203,18 @@ impl<'a> System<'a> for PotionUseSystem {
                     equip.insert(drink.potion, Equipped{owner: entity, slot: target_slot}).expect("Can't insert equipped item");
                     backpack.remove(drink.potion);
                     if entity == *player_entity {
-                        gamelog.entries.push(format!("You have equipped {}.", names.get(drink.potion).unwrap().name));
+                        let name = &names.get(drink.potion).unwrap().name;
+                        gamelog.entries.push(format!("You have equipped {}.", name));
+                        // Wearing it is how its curse or blessing comes to light
+                        if let Some(curse) = curses.get_mut(drink.potion) {
+                            curse.known = true;
+                            gamelog.entries.push(match curse.blessing {
+                                Blessing::Cursed => format!("The {} clings to you. It is cursed!", name),
+                                Blessing::Blessed => format!("The {} glows with a soft light. It is blessed.", name)
+                            });
+                        }
+                    } else if witnessed(&map, positions.get(entity)) {
+                        gamelog.entries.push(format!("{} equips the {}.", names.get(entity).unwrap().name, names.get(drink.potion).unwrap().name));
                     }
                 }
             }
@@ -102,14 +230,16 @@ impl<'a> System<'a> for ItemDropSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = ( ReadExpect<'a, Entity>,
                         WriteExpect<'a, Gamelog>,
+                        ReadExpect<'a, ItemNames>,
                         Entities<'a>,
                         WriteStorage<'a, WantsToDropItem>,
                         ReadStorage<'a, Name>,
                         WriteStorage<'a, Position>,
-                        WriteStorage<'a, InBackpack>);
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, Stack>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (player_entity, mut gamelog, entities, mut want_drop, names, mut positions, mut backpack) = data;
+        let (player_entity, mut gamelog, item_names, entities, mut want_drop, names, mut positions, mut backpack, stacks) = data;
 
         for (entity, to_drop) in (&entities, &want_drop).join() {
             let mut dropper_pos : Position = Position{x:0, y:0};
@@ -122,7 +252,7 @@ impl<'a> System<'a> for ItemDropSystem {
             backpack.remove(to_drop.item);
 
             if entity == *player_entity {
-                gamelog.entries.push(format!("You dropped the {}", names.get(to_drop.item).unwrap().name));
+                gamelog.entries.push(format!("You dropped the {}", counted(item_names.display(&names.get(to_drop.item).unwrap().name), stacks.get(to_drop.item))));
             }
         }
 
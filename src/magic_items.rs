@@ -0,0 +1,152 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use super::{Name, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus, WeaponDamage, Knockback, Resistances, Weight,
+    SpawnTable, CurseStatus, Blessing, raws::{self, Affix, AffixPosition}};
+
+/// Percent chance equipment comes out magic on depth 0, rising each depth
+const BASE_MAGIC_CHANCE : i32 = 10;
+const MAGIC_CHANCE_PER_DEPTH : i32 = 4;
+const MAX_ENCHANTMENT : i32 = 5;
+/// Percent chances equipment comes out cursed or blessed, whether magic or not
+const CURSE_CHANCE : i32 = 12;
+const BLESS_CHANCE : i32 = 10;
+/// How far a curse drags an item's enchantment down, and a blessing lifts it
+pub const CURSE_PENALTY : i32 = 2;
+const BLESSING_BONUS : i32 = 1;
+
+/// Sometimes turns freshly spawned equipment into a magic item, with an enchantment and affixes
+/// that grow likelier and stronger the deeper it is found, and renames it to match.
+/// Any equipment may also come out cursed or blessed.
+pub fn roll_magic(ecs : &mut World, item : Entity, depth : i32) {
+    let slot = match ecs.read_storage::<Equippable>().get(item) {
+        Some(equippable) => equippable.slot,
+        None => return
+    };
+    roll_blessing(ecs, item, slot);
+
+    let (mut enchantment, prefix, suffix) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        if rng.roll_dice(1, 100) > BASE_MAGIC_CHANCE + MAGIC_CHANCE_PER_DEPTH * depth {return;}
+        let enchantment = i32::min(rng.roll_dice(1, depth / 2 + 2) - 1, MAX_ENCHANTMENT);
+        let prefix = pick_affix(&mut rng, slot, AffixPosition::Prefix, depth);
+        let suffix = pick_affix(&mut rng, slot, AffixPosition::Suffix, depth);
+        (enchantment, prefix, suffix)
+    };
+    // Magic should always show
+    if enchantment == 0 && prefix.is_none() && suffix.is_none() {
+        enchantment = 1;
+    }
+
+    if enchantment > 0 {
+        enchant(ecs, item, slot, enchantment);
+    }
+    for affix in prefix.iter().chain(suffix.iter()) {
+        apply_affix(ecs, item, affix);
+    }
+
+    let mut names = ecs.write_storage::<Name>();
+    if let Some(name) = names.get_mut(item) {
+        let mut full = name.name.clone();
+        if let Some(prefix) = prefix {
+            full = format!("{} {}", prefix.name, full);
+        }
+        if enchantment > 0 {
+            full = format!("+{} {}", enchantment, full);
+        }
+        if let Some(suffix) = suffix {
+            full = format!("{} {}", full, suffix.name);
+        }
+        name.name = full;
+    }
+}
+
+/// Secretly curses or blesses equipment, shifting its enchantment without touching its name
+fn roll_blessing(ecs : &mut World, item : Entity, slot : EquipmentSlot) {
+    let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 100);
+    let blessing = if roll <= CURSE_CHANCE {
+        Blessing::Cursed
+    } else if roll <= CURSE_CHANCE + BLESS_CHANCE {
+        Blessing::Blessed
+    } else {
+        return;
+    };
+    enchant(ecs, item, slot, blessing_shift(blessing));
+    ecs.write_storage::<CurseStatus>().insert(item, CurseStatus{blessing, known: false}).expect("Unable to insert curse status");
+}
+
+/// How much a curse or blessing has moved an item's enchantment
+pub fn blessing_shift(blessing : Blessing) -> i32 {
+    match blessing {
+        Blessing::Cursed => -CURSE_PENALTY,
+        Blessing::Blessed => BLESSING_BONUS
+    }
+}
+
+/// Half the time nothing, otherwise a weighted pick among the affixes allowed for the slot and depth
+fn pick_affix(rng : &mut RandomNumberGenerator, slot : EquipmentSlot, position : AffixPosition, depth : i32) -> Option<&'static Affix> {
+    if rng.roll_dice(1, 2) == 1 {return None;}
+    let allowed = || raws::affixes().iter()
+        .filter(move |affix| affix.position == position && affix.slots.contains(&slot) && affix.min_depth <= depth);
+    let roll = allowed().fold(SpawnTable::new(), |table, affix| table.add(&affix.name, affix.weight)).roll(rng);
+    allowed().find(|affix| affix.name == roll)
+}
+
+/// A +N enchantment sharpens weapons and toughens shields
+fn enchant(ecs : &mut World, item : Entity, slot : EquipmentSlot, amount : i32) {
+    adjust_enchantment(item, slot, amount, &mut ecs.write_storage(), &mut ecs.write_storage());
+}
+
+/// Shifts an item's enchantment by `amount`, for callers that already hold the storages.
+/// Melee adds power on top of the weapon's dice, so the bonus goes on power alone.
+pub fn adjust_enchantment(item : Entity, slot : EquipmentSlot, amount : i32, power : &mut WriteStorage<MeleePowerBonus>,
+    defense : &mut WriteStorage<DefenseBonus>)
+{
+    match slot {
+        EquipmentSlot::Weapon => {
+            power.entry(item).expect("Unable to add power bonus").or_insert(MeleePowerBonus{power: 0}).power += amount;
+        }
+        EquipmentSlot::Shield => {
+            defense.entry(item).expect("Unable to add defense bonus").or_insert(DefenseBonus{defense: 0}).defense += amount;
+        }
+    }
+}
+
+fn apply_affix(ecs : &mut World, item : Entity, affix : &Affix) {
+    if affix.power != 0 {
+        add_power(ecs, item, affix.power);
+    }
+    if affix.defense != 0 {
+        add_defense(ecs, item, affix.defense);
+    }
+    if affix.knockback != 0 {
+        let mut knockback = ecs.write_storage::<Knockback>();
+        knockback.entry(item).expect("Unable to add knockback").or_insert(Knockback{distance: 0}).distance += affix.knockback;
+    }
+    if let Some(kind) = affix.damage_kind {
+        if let Some(weapon) = ecs.write_storage::<WeaponDamage>().get_mut(item) {
+            weapon.kind = kind;
+        }
+    }
+    if let Some(kind) = affix.resist {
+        let mut resistances = ecs.write_storage::<Resistances>();
+        let resists = resistances.entry(item).expect("Unable to add resistance").or_insert_with(Resistances::default);
+        if !resists.resistant.contains(&kind) {
+            resists.resistant.push(kind);
+        }
+    }
+    if let Some(scale) = affix.weight_scale {
+        if let Some(weight) = ecs.write_storage::<Weight>().get_mut(item) {
+            weight.weight *= scale;
+        }
+    }
+}
+
+fn add_power(ecs : &mut World, item : Entity, amount : i32) {
+    let mut bonuses = ecs.write_storage::<MeleePowerBonus>();
+    bonuses.entry(item).expect("Unable to add power bonus").or_insert(MeleePowerBonus{power: 0}).power += amount;
+}
+
+fn add_defense(ecs : &mut World, item : Entity, amount : i32) {
+    let mut bonuses = ecs.write_storage::<DefenseBonus>();
+    bonuses.entry(item).expect("Unable to add defense bonus").or_insert(DefenseBonus{defense: 0}).defense += amount;
+}
\ No newline at end of file
@@ -11,7 +11,7 @@ pub use rect::Rect;
 mod fov;
 use fov::FovSystem;
 mod monster_ai_system;
-use monster_ai_system::MonsterAI;
+use monster_ai_system::{MonsterAI, ChasePathing};
 mod map_indexing;
 use map_indexing::MapIndexSystem;
 mod melee_combat_system;
@@ -22,14 +22,40 @@ mod gui;
 mod gamelog;
 mod spawner;
 mod inventory_system;
-use inventory_system::{ItemCollectionSystem, PotionUseSystem, ItemDropSystem};
+use inventory_system::{ItemCollectionSystem, GoldCollectionSystem, PotionUseSystem, ItemDropSystem};
 mod spawn_table;
 pub use spawn_table::SpawnTable;
+mod ability_system;
+use ability_system::AbilitySystem;
+mod noise_system;
+use noise_system::{NoiseSystem, NoiseEvents};
+mod bench;
+mod experience;
+mod particle_system;
+mod forced_movement_system;
+mod hunger_system;
+mod ongoing_action;
+mod raws;
+mod identification;
+mod encumbrance_system;
+mod magic_items;
+mod scroll_system;
+mod wand_system;
+mod containers;
+mod shop;
+use hunger_system::HungerSystem;
+use forced_movement_system::ForcedMovementSystem;
+use particle_system::{ParticleSpawnSystem, ParticleBuilder};
+use encumbrance_system::EncumbranceSystem;
+use scroll_system::ScrollSystem;
+use wand_system::WandSystem;
 
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {AwaitingInput, PreRun, PlayerTurn, MonsterTurn,
-	ShowInventory, ShowDropItem, Menu {selection: gui::MenuSelection}, NextLevel}
+	ShowInventory {page: usize}, ShowDropItem {page: usize}, Menu {selection: gui::MenuSelection}, ShowTargeting {range: i32, item: Entity},
+	ShowContainer {container: Entity, page: usize}, ShowShop {merchant: Entity, selling: bool, page: usize},
+	ConfirmAttack {target: Entity}, NextLevel, LevelUp, GameOver}
 
 pub struct State {
 	pub ecs: World
@@ -41,8 +67,11 @@ impl State {
 		// FOV
 		let mut fov = FovSystem{};
 		fov.run_now(&self.ecs);
+		// Noise
+		let mut noise = NoiseSystem{};
+		noise.run_now(&self.ecs);
 		// Monster
-		let mut mob = MonsterAI{};
+		let mut mob = MonsterAI{pathing: ChasePathing::Shared};
 		mob.run_now(&self.ecs);
 		// BlockedList
 		let mut mapidx = MapIndexSystem{};
@@ -50,18 +79,41 @@ impl State {
 		// Melee System
 		let mut melee = MeleeCombatSystem{};
 		melee.run_now(&self.ecs);
+		// Monster Abilities
+		let mut abilities = AbilitySystem{};
+		abilities.run_now(&self.ecs);
+		// Hunger
+		let mut hunger = HungerSystem{};
+		hunger.run_now(&self.ecs);
+		// Shoves and knockback
+		let mut forced = ForcedMovementSystem{};
+		forced.run_now(&self.ecs);
 		// Damage System
 		let mut damage = DamageSystem{};
 		damage.run_now(&self.ecs);
 		// Item Pickup System
 		let mut pickup = ItemCollectionSystem{};
 		pickup.run_now(&self.ecs);
+		let mut gold = GoldCollectionSystem{};
+		gold.run_now(&self.ecs);
 		// Potions
 		let mut potions = PotionUseSystem{};
 		potions.run_now(&self.ecs);
+		// Scrolls
+		let mut scrolls = ScrollSystem{};
+		scrolls.run_now(&self.ecs);
+		// Wands
+		let mut wands = WandSystem{};
+		wands.run_now(&self.ecs);
 		// Dropping Items
 		let mut drop_items = ItemDropSystem{};
 		drop_items.run_now(&self.ecs);
+		// Carried weight
+		let mut encumbrance = EncumbranceSystem{};
+		encumbrance.run_now(&self.ecs);
+		// Visual effects queued by the systems above
+		let mut particles = ParticleSpawnSystem{};
+		particles.run_now(&self.ecs);
 
 		self.ecs.maintain();
 	}
@@ -106,6 +158,13 @@ impl State {
 	}
 
 
+	/// Throws the whole world away and rolls a fresh run
+	fn new_game(&mut self) {
+		self.ecs = World::new();
+		register_components(&mut self.ecs);
+		new_run(&mut self.ecs);
+	}
+
 	fn next_level(&mut self) {
 		// Deleting entities that aren't player and inventory
 		let to_delete = self.clean_level_change();
@@ -115,17 +174,14 @@ impl State {
 
 		// New map generation
 		let new_level;
-		let curr_depth: i32;
 		{
 			let mut map = self.ecs.write_resource::<Map>();
-			curr_depth = map.depth;
-			*map = Map::new_map_rooms_and_corridors(curr_depth + 1);
+			let mut rng = self.ecs.write_resource::<rltk::RandomNumberGenerator>();
+			*map = build_map(map.depth + 1, &mut rng);
 			new_level = map.clone();
 		}
 		// Monster Entities
-		for room in new_level.rooms.iter().skip(1) {
-			spawner::spawn_room(&mut self.ecs, room, curr_depth + 1);
-		}
+		spawner::spawn_level(&mut self.ecs, &new_level);
 		// Player position
 		let (p_x, p_y) = new_level.rooms[0].center();
 		let mut player_pos = self.ecs.write_resource::<Point>();
@@ -163,11 +219,12 @@ impl GameState for State {
 		}
 
         ctx.cls();
+		particle_system::cull_dead_particles(&mut self.ecs, ctx);
 
 
 		// Matching States for Turns and Item menus
 		match newrunstate {
-			RunState::Menu{..} => {}
+			RunState::Menu{..} | RunState::GameOver => {}
 			_ => {
 				// map needs to be drawn first
 				draw_map(&self.ecs, ctx);
@@ -177,12 +234,16 @@ impl GameState for State {
 				{
 					let pos = self.ecs.read_storage::<Position>();
 					let ren = self.ecs.read_storage::<Renderable>();
+					let particles = self.ecs.read_storage::<ParticleLifetime>();
 					let map = self.ecs.fetch::<Map>();
 
-					// Render Entities with both a Position and Renderable Component
-					let mut data = (&pos, &ren).join().collect::<Vec<_>>();
-					data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
-					for (p, r) in data.iter() {
+					// Render Entities with both a Position and Renderable Component,
+					// holding back particles that are still waiting to appear
+					let mut data = (&pos, &ren, particles.maybe()).join()
+						.filter(|(_p, _r, particle)| particle.is_none_or(|particle| particle.delay_ms <= 0.0))
+						.collect::<Vec<_>>();
+					data.sort_by_key(|&a| std::cmp::Reverse(a.1.render_order));
+					for (p, r, _particle) in data.iter() {
 						let idx = map.xy_idx(p.x, p.y);
 						if map.visible_tiles[idx] {ctx.set(p.x, p.y, r.fg, r.bg, r.glyph)}
 					}
@@ -199,40 +260,100 @@ impl GameState for State {
 				newrunstate = RunState::AwaitingInput;
 			}
 			RunState::AwaitingInput => {
-				newrunstate = keyboard(self, ctx);
+				newrunstate = match ongoing_action::resume(&mut self.ecs, ctx.key.is_some()) {
+					Some(state) => state,
+					None => keyboard(self, ctx)
+				};
 			}
 			RunState::PlayerTurn => {
+				self.ecs.write_resource::<gamelog::RunStats>().turns += 1;
 				self.run_systems();
 				self.ecs.maintain();
 				newrunstate = RunState::MonsterTurn;
 			}
 			RunState::MonsterTurn => {
 				self.run_systems();
+				damage_system::decay_corpses(&mut self.ecs);
 				self.ecs.maintain();
-				newrunstate = RunState::AwaitingInput;
+				// Monsters catch up on an overburdened or slowed player. Both clear state, so both always run.
+				let encumbered = encumbrance_system::lags_behind(&mut self.ecs);
+				let slowed = player::slowed_lags(&mut self.ecs);
+				newrunstate = if encumbered || slowed {RunState::MonsterTurn} else {RunState::AwaitingInput};
 			}
 			RunState::NextLevel => {
 				self.next_level();
 				newrunstate = RunState::PreRun;
 			}
-			RunState::ShowInventory => {
-				let result = gui::show_inventory(self, ctx);
+			RunState::ShowInventory{page} => {
+				let result = gui::show_inventory(self, ctx, page);
 				match result.0 {
 					gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
 					gui::ItemMenuResult::NoResponse => {}
+					gui::ItemMenuResult::Page(page) => newrunstate = RunState::ShowInventory{page},
 					gui::ItemMenuResult::Selected => {
 						let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDrinkPotion>();
-                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToDrinkPotion{ potion: item_entity }).expect("Unable to insert intent");
-                        newrunstate = RunState::PlayerTurn;
+						let is_scroll = self.ecs.read_storage::<Scroll>().get(item_entity).is_some();
+						let range = self.ecs.read_storage::<Ranged>().get(item_entity).map(|ranged| ranged.range);
+						if let Some(range) = range {
+							newrunstate = RunState::ShowTargeting{range, item: item_entity};
+						} else if is_scroll {
+							let mut intent = self.ecs.write_storage::<WantsToReadScroll>();
+							intent.insert(*self.ecs.fetch::<Entity>(), WantsToReadScroll{scroll: item_entity, target: None}).expect("Unable to insert intent");
+							newrunstate = RunState::PlayerTurn;
+						} else {
+							let mut intent = self.ecs.write_storage::<WantsToDrinkPotion>();
+							intent.insert(*self.ecs.fetch::<Entity>(), WantsToDrinkPotion{ potion: item_entity }).expect("Unable to insert intent");
+							newrunstate = RunState::PlayerTurn;
+						}
+					}
+				}
+			}
+			RunState::ShowContainer{container, page} => {
+				let result = gui::show_container(self, ctx, container, page);
+				match result.0 {
+					gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+					gui::ItemMenuResult::NoResponse => {}
+					gui::ItemMenuResult::Page(page) => newrunstate = RunState::ShowContainer{container, page},
+					gui::ItemMenuResult::Selected => {
+						let player_entity = *self.ecs.fetch::<Entity>();
+						let mut intent = self.ecs.write_storage::<WantsToPickupItem>();
+						intent.insert(player_entity, WantsToPickupItem{collected_by: player_entity, item: result.1.unwrap()}).expect("Unable to insert intent");
+						newrunstate = RunState::PlayerTurn;
+					}
+				}
+			}
+			RunState::ShowShop{merchant, selling, page} => {
+				match gui::show_shop(self, ctx, merchant, selling, page) {
+					gui::ShopResult::Leave => newrunstate = RunState::AwaitingInput,
+					gui::ShopResult::NoResponse => {}
+					gui::ShopResult::Page(page) => newrunstate = RunState::ShowShop{merchant, selling, page},
+					gui::ShopResult::SwitchMode => newrunstate = RunState::ShowShop{merchant, selling: !selling, page: 0},
+					gui::ShopResult::Trade(item) => {
+						if selling {
+							shop::sell(&mut self.ecs, merchant, item);
+						} else {
+							shop::buy(&mut self.ecs, merchant, item);
+						}
+						self.ecs.maintain();
+					}
+				}
+			}
+			RunState::ConfirmAttack{target} => {
+				match gui::confirm_attack(self, ctx, target) {
+					gui::ConfirmResult::NoResponse => {}
+					gui::ConfirmResult::No => newrunstate = RunState::AwaitingInput,
+					gui::ConfirmResult::Yes => {
+						shop::provoke(&mut self.ecs, target);
+						newrunstate = RunState::PlayerTurn;
 					}
 				}
 			}
-			RunState::ShowDropItem => {
-				let result = gui::drop_item_menu(self, ctx);
+			RunState::ShowDropItem{page} => {
+				let result = gui::drop_item_menu(self, ctx, page);
 				match result.0 {
 					gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
 					gui::ItemMenuResult::NoResponse => {}
+					gui::ItemMenuResult::Page(page) => newrunstate = RunState::ShowDropItem{page},
 					gui::ItemMenuResult::Selected => {
 						let item_entity = result.1.unwrap();
 						let mut intent = self.ecs.write_storage::<WantsToDropItem>();
@@ -241,6 +362,42 @@ impl GameState for State {
 					}
 				}
 			}
+			RunState::ShowTargeting{range, item} => {
+				let result = gui::ranged_target(self, ctx, range);
+				match result.0 {
+					gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+					gui::ItemMenuResult::NoResponse | gui::ItemMenuResult::Page(_) => {}
+					gui::ItemMenuResult::Selected => {
+						let player_entity = *self.ecs.fetch::<Entity>();
+						let target = result.1.unwrap();
+						if self.ecs.read_storage::<Wand>().get(item).is_some() {
+							let mut intent = self.ecs.write_storage::<WantsToZap>();
+							intent.insert(player_entity, WantsToZap{wand: item, target}).expect("Unable to insert intent");
+						} else {
+							let mut intent = self.ecs.write_storage::<WantsToReadScroll>();
+							intent.insert(player_entity, WantsToReadScroll{scroll: item, target: Some(target)}).expect("Unable to insert intent");
+						}
+						newrunstate = RunState::PlayerTurn;
+					}
+				}
+			}
+			RunState::LevelUp => {
+				let result = gui::level_up_menu(self, ctx);
+				match result.0 {
+					gui::ItemMenuResult::Cancel | gui::ItemMenuResult::NoResponse | gui::ItemMenuResult::Page(_) => {}
+					gui::ItemMenuResult::Selected => {
+						if !experience::apply(&mut self.ecs, result.1.unwrap()) {
+							newrunstate = RunState::MonsterTurn;
+						}
+					}
+				}
+			}
+			RunState::GameOver => {
+				if gui::game_over(&self.ecs, ctx) == gui::GameOverResult::QuitToMenu {
+					self.new_game();
+					newrunstate = RunState::Menu{selection: gui::MenuSelection::NewGame};
+				}
+			}
 			RunState::Menu{..} => {
 				let result = gui::menu(self, ctx);
 				match result {
@@ -266,7 +423,127 @@ impl GameState for State {
 }
 
 
+/// Builds the map for a depth, carving out a lair where a boss is due.
+/// A level with too few rooms for a lair is thrown away and dug again, so the boss is never skipped.
+fn build_map(depth: i32, rng: &mut rltk::RandomNumberGenerator) -> Map {
+	loop {
+		let mut map = Map::new_map_rooms_and_corridors(depth, rng);
+		if raws::boss_for_depth(depth).is_none() || map.add_boss_lair(rng) {
+			return map;
+		}
+	}
+}
+
+/// Generates the first level, the player and every run resource, leaving the game at the main menu
+fn new_run(ecs: &mut World) {
+	// One seed drives the levels and what unidentified items look like
+	let seed = rltk::RandomNumberGenerator::new().next_u64();
+	let mut rng = rltk::RandomNumberGenerator::seeded(seed);
+	let map : Map = build_map(1, &mut rng);
+	let (player_x, player_y) = map.rooms[0].center();
+
+	let player_entity = spawner::player(ecs, player_x, player_y);
+
+	// Must insert Map after all other uses
+	// resources
+	ecs.insert(rng);
+	ecs.insert(NoiseEvents::default());
+	ecs.insert(ParticleBuilder::default());
+	ecs.insert(spawner::BossTracker::default());
+	ecs.insert(spawner::PackIds::default());
+	ecs.insert(identification::ItemNames::new(seed));
+	ecs.insert(gamelog::RunStats::default());
+	ecs.insert(gamelog::Gamelog{entries : vec!["Welcome to Belsin!".to_string()]});
+	// Spawning reads the depth off the Map resource, so it goes in first
+	let level = map.clone();
+	ecs.insert(map);
+	spawner::spawn_level(ecs, &level);
+	ecs.insert(Point::new(player_x, player_y));
+	ecs.insert(player_entity);
+	ecs.insert(RunState::Menu{selection: gui::MenuSelection::NewGame});
+}
+
+/// Register all components in the Entity Component System in GameState
+fn register_components(ecs: &mut World) {
+	ecs.register::<Position>();
+	ecs.register::<Renderable>();
+	ecs.register::<Player>();
+	ecs.register::<Fov>();
+	ecs.register::<Monster>();
+	ecs.register::<Name>();
+	ecs.register::<BlocksTile>();
+	ecs.register::<CombatStats>();
+	ecs.register::<AttackIntent>();
+	ecs.register::<SufferDamage>();
+	ecs.register::<Item>();
+	ecs.register::<Potion>();
+	ecs.register::<InBackpack>();
+	ecs.register::<WantsToPickupItem>();
+	ecs.register::<WantsToDrinkPotion>();
+	ecs.register::<WantsToDropItem>();
+	ecs.register::<Equippable>();
+	ecs.register::<Equipped>();
+	ecs.register::<Abilities>();
+	ecs.register::<WantsToUseAbility>();
+	ecs.register::<Pack>();
+	ecs.register::<Alerted>();
+	ecs.register::<MeleePowerBonus>();
+	ecs.register::<WeaponDamage>();
+	ecs.register::<Resistances>();
+	ecs.register::<LastHit>();
+	ecs.register::<Experience>();
+	ecs.register::<XpValue>();
+	ecs.register::<Perks>();
+	ecs.register::<Corpse>();
+	ecs.register::<ParticleLifetime>();
+	ecs.register::<ForcedMove>();
+	ecs.register::<Knockback>();
+	ecs.register::<Friendly>();
+	ecs.register::<HungerClock>();
+	ecs.register::<ProvidesFood>();
+	ecs.register::<Stack>();
+	ecs.register::<Weight>();
+	ecs.register::<Encumbrance>();
+	ecs.register::<WantsToReadScroll>();
+	ecs.register::<Scroll>();
+	ecs.register::<MagicMapper>();
+	ecs.register::<Teleports>();
+	ecs.register::<Identifies>();
+	ecs.register::<Ranged>();
+	ecs.register::<AreaOfEffect>();
+	ecs.register::<InflictsDamage>();
+	ecs.register::<Wand>();
+	ecs.register::<Bolt>();
+	ecs.register::<WantsToZap>();
+	ecs.register::<Slowed>();
+	ecs.register::<Container>();
+	ecs.register::<Trapped>();
+	ecs.register::<InContainer>();
+	ecs.register::<Key>();
+	ecs.register::<Gold>();
+	ecs.register::<Purse>();
+	ecs.register::<Merchant>();
+	ecs.register::<CurseStatus>();
+	ecs.register::<RemovesCurse>();
+	ecs.register::<OngoingAction>();
+	ecs.register::<DefenseBonus>();
+	ecs.register::<UsesItems>();
+	ecs.register::<Awareness>();
+	ecs.register::<PathCache>();
+	ecs.register::<Boss>();
+}
+
 fn main() -> rltk::BError {
+	if let Err(e) = raws::load() {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	}
+
+	if std::env::args().any(|arg| arg == "--bench-ai") {
+		bench::run();
+		return Ok(());
+	}
+
     use rltk::RltkBuilder;
     let mut context = RltkBuilder::simple80x50()
         .with_title("Belsin")
@@ -277,44 +554,8 @@ fn main() -> rltk::BError {
     let mut gs = State{
 		ecs: World::new(),
 	};
-	// Register all components in the Entity Component System in GameState
-	gs.ecs.register::<Position>();
-	gs.ecs.register::<Renderable>();
-	gs.ecs.register::<Player>();
-	gs.ecs.register::<Fov>();
-	gs.ecs.register::<Monster>();
-	gs.ecs.register::<Name>();
-	gs.ecs.register::<BlocksTile>();
-	gs.ecs.register::<CombatStats>();
-	gs.ecs.register::<AttackIntent>();
-	gs.ecs.register::<SufferDamage>();
-	gs.ecs.register::<Item>();
-	gs.ecs.register::<Potion>();
-	gs.ecs.register::<InBackpack>();
-	gs.ecs.register::<WantsToPickupItem>();
-	gs.ecs.register::<WantsToDrinkPotion>();
-	gs.ecs.register::<WantsToDropItem>();
-	gs.ecs.register::<Equippable>();
-	gs.ecs.register::<Equipped>();
-		
-	// Map making
-	let map : Map = Map::new_map_rooms_and_corridors(1);
-	let (player_x, player_y) = map.rooms[0].center();
-
-	let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
-
-	// Must insert Map after all other uses
-	// resources
-	gs.ecs.insert(rltk::RandomNumberGenerator::new());
-	for room in map.rooms.iter().skip(1) {
-		spawner::spawn_room(&mut gs.ecs, room, 1);
-	}
-	gs.ecs.insert(map);
-	gs.ecs.insert(Point::new(player_x, player_y));
-	gs.ecs.insert(player_entity);
-	gs.ecs.insert(RunState::Menu{selection: gui::MenuSelection::NewGame});
-	gs.ecs.insert(gamelog::Gamelog{entries : vec!["Welcome to Belsin!".to_string()]});
-	// gs.ecs.insert(InBackpack::insert(potion, owner: player_entity));
+	register_components(&mut gs.ecs);
+	new_run(&mut gs.ecs);
 
     rltk::main_loop(context, gs)
 }
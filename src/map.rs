@@ -9,7 +9,7 @@ pub const MAPCOUNT : usize = MAPWIDTH*MAPHEIGHT;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum TileType {
-	Wall, Floor, DownStairs
+	Wall, Floor, DownStairs, HiddenStairs
 }
 
 #[derive(Default, Clone)]
@@ -22,7 +22,8 @@ pub struct Map {
 	pub visible_tiles : Vec<bool>,
 	pub blocked : Vec<bool>,
 	pub tile_content : Vec<Vec<Entity>>,
-	pub depth : i32
+	pub depth : i32,
+	pub boss_room : Option<Rect>
 }
 
 impl Map {
@@ -43,7 +44,7 @@ impl Map {
 		for x in min(x1, x2) ..= max(x1, x2) {
 			let idx = self.xy_idx(x, y);
 			if idx > 0 && idx < self.width as usize * self.height as usize {
-				self.tiles[idx as usize] = TileType::Floor;
+				self.tiles[idx] = TileType::Floor;
 			}
 		}
 	}
@@ -52,7 +53,7 @@ impl Map {
 		for y in min(y1, y2) ..= max(y1, y2) {
 			let idx = self.xy_idx(x, y);
 			if idx > 0 && idx < self.width as usize * self.height as usize {
-				self.tiles[idx as usize] = TileType::Floor;
+				self.tiles[idx] = TileType::Floor;
 			}
 		}
 	}
@@ -71,6 +72,24 @@ impl Map {
 		}
 	}
 
+	/// Walkable tiles that lie outside every room
+	pub fn is_corridor(&self, idx: usize) -> bool {
+		if self.tiles[idx] == TileType::Wall {return false;}
+		let x = idx as i32 % self.width;
+		let y = idx as i32 / self.width;
+		!self.rooms.iter().any(|room| x > room.x1 && x <= room.x2 && y > room.y1 && y <= room.y2)
+	}
+
+	/// Moves an entity between tiles, keeping `blocked` and `tile_content` in step
+	pub fn move_entity(&mut self, entity: Entity, from: usize, to: usize, blocks: bool) {
+		self.tile_content[from].retain(|other| *other != entity);
+		self.tile_content[to].push(entity);
+		if blocks {
+			self.blocked[from] = false;
+			self.blocked[to] = true;
+		}
+	}
+
 	/// Clears every vector in the tile_content vector
 	pub fn clear_content_index(&mut self) {
 		for content in self.tile_content.iter_mut() {
@@ -78,7 +97,7 @@ impl Map {
 		}
 	}
 
-	pub fn new_map_rooms_and_corridors(new_depth: i32) -> Map {
+	pub fn new_map_rooms_and_corridors(new_depth: i32, rng: &mut RandomNumberGenerator) -> Map {
 		// Map Initializer
 		let mut map = Map {
 			tiles : vec![TileType::Wall; MAPCOUNT],
@@ -89,15 +108,14 @@ impl Map {
 			visible_tiles : vec![false; MAPCOUNT],
 			blocked : vec![false; MAPCOUNT],
 			tile_content : vec![Vec::new(); MAPCOUNT],
-			depth: new_depth
+			depth: new_depth,
+			boss_room: None
 		};
 
 		const MAX_ROOMS : i32 = 30;
 		const MIN_SIZE : i32 = 6;
 		const MAX_SIZE : i32 = 10;
 
-		let mut rng = RandomNumberGenerator::new();
-
 		for _ in 0..MAX_ROOMS {
 			let w = rng.range(MIN_SIZE, MAX_SIZE);
 			let h = rng.range(MIN_SIZE, MAX_SIZE);
@@ -132,6 +150,51 @@ impl Map {
 
 		map
 	}
+
+	/// Makes the biggest room a boss lair holding the stairs, and hides a second way down in another room.
+	/// Leaves the map alone and returns false if it lacks a start room, a lair and a room for the hidden stairs.
+	pub fn add_boss_lair(&mut self, rng: &mut RandomNumberGenerator) -> bool {
+		if self.rooms.len() < 3 {return false;}
+
+		let last = self.rooms[self.rooms.len() - 1].center();
+		let old_stairs = self.xy_idx(last.0, last.1);
+		self.tiles[old_stairs] = TileType::Floor;
+
+		let lair = *self.rooms.iter().skip(1)
+			.max_by_key(|room| (room.x2 - room.x1) * (room.y2 - room.y1))
+			.unwrap();
+		let (stair_x, stair_y) = lair.center();
+		let stair_idx = self.xy_idx(stair_x, stair_y);
+		self.tiles[stair_idx] = TileType::DownStairs;
+		self.boss_room = Some(lair);
+
+		let others : Vec<Rect> = self.rooms.iter().skip(1).filter(|room| **room != lair).cloned().collect();
+		let room = others[(rng.roll_dice(1, others.len() as i32) - 1) as usize];
+		let x = room.x1 + rng.roll_dice(1, room.x2 - room.x1);
+		let y = room.y1 + rng.roll_dice(1, room.y2 - room.y1);
+		let hidden_idx = self.xy_idx(x, y);
+		self.tiles[hidden_idx] = TileType::HiddenStairs;
+		true
+	}
+
+	pub fn in_boss_room(&self, x: i32, y: i32) -> bool {
+		self.boss_room.is_some_and(|room| x > room.x1 && x <= room.x2 && y > room.y1 && y <= room.y2)
+	}
+
+	/// Uncovers hidden stairs next to the given tile, returning true if any were found
+	pub fn reveal_hidden_stairs(&mut self, x: i32, y: i32) -> bool {
+		let mut found = false;
+		for dy in -1 ..= 1 {
+			for dx in -1 ..= 1 {
+				let idx = self.xy_idx(x + dx, y + dy);
+				if self.tiles[idx] == TileType::HiddenStairs {
+					self.tiles[idx] = TileType::DownStairs;
+					found = true;
+				}
+			}
+		}
+		found
+	}
 }
 
 impl Algorithm2D for Map {
@@ -166,7 +229,7 @@ impl BaseMap for Map {
 		exits
 	}
 	fn get_pathing_distance(&self, idx1:usize, idx2:usize) -> f32 {
-		let w = MAPWIDTH as usize;
+		let w = MAPWIDTH;
 		let p1 = Point::new(idx1 % w, idx1 / w);
 		let p2 = Point::new(idx2 % w, idx2 / w);
 		rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
@@ -184,7 +247,7 @@ pub fn draw_map(ecs : &World, ctx : &mut Rltk) {
 			let glyph;
 			let mut fg;
 			match tile {
-				TileType::Floor => {
+				TileType::Floor | TileType::HiddenStairs => {
 					glyph = rltk::to_cp437('.');
 					fg = RGB::from_f32(0.0, 0.5, 0.5);
 				}
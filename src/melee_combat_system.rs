@@ -1,32 +1,119 @@
 use specs::prelude::*;
-use super::{CombatStats, AttackIntent, Name, SufferDamage, gamelog::Gamelog};
+use rltk::{RandomNumberGenerator, RGB};
+use super::{CombatStats, AttackIntent, Name, SufferDamage, gamelog::Gamelog, Equipped, MeleePowerBonus,
+    DefenseBonus, Awareness, AwarenessState, Position, WeaponDamage, Perks, Perk, noise_system::{NoiseEvents, COMBAT_NOISE},
+    particle_system::ParticleBuilder, Knockback, ForcedMove, Boss};
+
+/// An attack roll below this misses outright; one below it plus armor is blocked
+const EVASION : i32 = 10;
 
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = ( Entities<'a>,
                         WriteExpect<'a, Gamelog>,
+                        WriteExpect<'a, RandomNumberGenerator>,
                         WriteStorage<'a, AttackIntent>,
                         ReadStorage<'a, CombatStats>,
                         ReadStorage<'a, Name>,
-                        WriteStorage<'a, SufferDamage>);
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenseBonus>,
+                        ReadStorage<'a, WeaponDamage>,
+                        ReadStorage<'a, Perks>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, Position>,
+                        WriteExpect<'a, NoiseEvents>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        ReadStorage<'a, Knockback>,
+                        WriteStorage<'a, ForcedMove>,
+                        ReadStorage<'a, Boss>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (entities, mut log, mut atk_int, combat_stats, names, mut inflict_damage) = data;
+        let (entities, mut log, mut rng, mut atk_int, combat_stats, names, mut inflict_damage, equipped, power_bonus,
+            defense_bonus, weapon_damage, perks, mut awareness, positions, mut noise, mut particles, knockback, mut forced_moves,
+            bosses) = data;
 
-        for (_entity, atk_int, name, stats) in (&entities, &atk_int, &names, &combat_stats).join() {
+        for (entity, atk_int, name, stats) in (&entities, &atk_int, &names, &combat_stats).join() {
             if stats.hp > 0 {
                 let target_stats = combat_stats.get(atk_int.target).unwrap();
                 if target_stats.hp > 0 {
                     let target_name = names.get(atk_int.target).unwrap();
 
-                    let damage = i32::max(0, stats.power - target_stats.defense);
+                    // Bonuses from whatever the attacker and target have equipped
+                    let power : i32 = (&equipped, &power_bonus).join()
+                        .filter(|(eq, _)| eq.owner == entity)
+                        .map(|(_, bonus)| bonus.power)
+                        .sum();
+                    let defense : i32 = (&equipped, &defense_bonus).join()
+                        .filter(|(eq, _)| eq.owner == atk_int.target)
+                        .map(|(_, bonus)| bonus.defense)
+                        .sum();
+                    let weapon = (&entities, &equipped, &weapon_damage).join()
+                        .find(|(_, eq, _)| eq.owner == entity);
+                    let (dice, kind) = weapon.map_or((stats.damage, stats.damage_type), |(_, _, w)| (w.dice, w.kind));
+                    let cause = weapon.and_then(|(item, _, _)| names.get(item)).map_or("bare hands", |n| n.name.as_str());
+
+                    if let Some(pos) = positions.get(entity) {
+                        noise.emit(pos.x, pos.y, COMBAT_NOISE);
+                    }
+
+                    // Catching a target asleep or unaware always lands, and wakes it
+                    let mut sneak = false;
+                    if let Some(target_awareness) = awareness.get_mut(atk_int.target) {
+                        if target_awareness.state != AwarenessState::Aware {
+                            target_awareness.state = AwarenessState::Aware;
+                            sneak = true;
+                        }
+                    }
+
+                    let natural = rng.roll_dice(1, 20);
+                    let attack = natural + stats.power + power;
+                    let has_perk = |perk : Perk| perks.get(entity).is_some_and(|p| p.list.contains(&perk));
+                    let critical = natural == 20 || (natural == 19 && has_perk(Perk::KeenEye));
+                    if !sneak && !critical {
+                        if natural == 1 && !has_perk(Perk::Steady) {
+                            log.entries.push(format!("{} fumbles the attack on {}", &name.name, &target_name.name));
+                            continue;
+                        }
+                        if attack < EVASION {
+                            log.entries.push(format!("{} misses {}", &name.name, &target_name.name));
+                            continue;
+                        }
+                        if attack < EVASION + target_stats.defense + defense {
+                            log.entries.push(format!("{}'s armor blocks the blow from {}", &target_name.name, &name.name));
+                            continue;
+                        }
+                    }
+
+                    let mut damage = dice.roll(&mut rng) + power;
+                    if critical || sneak {
+                        damage += dice.roll(&mut rng) - dice.bonus;
+                    }
+                    let damage = i32::max(1, damage);
 
-                    if damage == 0 {
-                        log.entries.push(format!("{} Unable to hurt {}", &name.name, &target_name.name));
+                    if sneak {
+                        log.entries.push(format!("{} sneak attacks {}, for {} hp!", &name.name, &target_name.name, damage));
+                    } else if critical {
+                        log.entries.push(format!("{} critically hits {}, for {} hp!", &name.name, &target_name.name, damage));
                     } else {
                         log.entries.push(format!("{} hits {}, for {} hp", &name.name, &target_name.name, damage));
-                        SufferDamage::new_damage(&mut inflict_damage, atk_int.target, damage);
+                    }
+                    SufferDamage::new_damage(&mut inflict_damage, atk_int.target, damage, kind, Some(entity), cause);
+                    if let Some(pos) = positions.get(atk_int.target) {
+                        particles.flash(pos.x, pos.y, RGB::named(rltk::ORANGE));
+                    }
+
+                    // Heavy weapons drive the target straight back from the attacker
+                    let knockback = weapon.and_then(|(item, _, _)| knockback.get(item));
+                    if let (Some(knockback), Some(from), Some(to)) = (knockback, positions.get(entity), positions.get(atk_int.target)) {
+                        if bosses.get(atk_int.target).is_none() {
+                            let (dx, dy) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+                            forced_moves.insert(atk_int.target, ForcedMove{dx, dy, distance: knockback.distance, source: Some(entity)})
+                                .expect("Unable to insert forced move");
+                        }
                     }
                 }
             }
@@ -1,8 +1,71 @@
 use specs::prelude::*;
-use super::{Fov, Monster, Map, Position, AttackIntent, RunState};
-use rltk::{Point};
+use super::{Fov, Monster, Map, Position, AttackIntent, RunState, CombatStats, Abilities, AbilityEffect,
+    WantsToUseAbility, Pack, Alerted, Name, gamelog::Gamelog, Item, InBackpack, Equipped, Equippable, Potion,
+    MeleePowerBonus, DefenseBonus, UsesItems, WantsToPickupItem, WantsToDrinkPotion, Awareness, AwarenessState,
+    noise_system::{NoiseEvents, SHOUT_NOISE}, PathCache, fov::ACTIVE_RADIUS, particle_system::ParticleBuilder,
+    Wand, Bolt, BoltEffect, Ranged, WantsToZap, Slowed, CurseStatus, Blessing};
+use rltk::{Point, RandomNumberGenerator, BaseMap, DijkstraMap, RGB};
+use std::collections::{HashMap, HashSet};
 
-pub struct MonsterAI {}
+/// How far the shared chase map spreads out from the player
+const MAX_CHASE_DEPTH : f32 = 60.0;
+/// Goals further than this in a straight line are not worth searching for
+const MAX_PATH_DISTANCE : f32 = 48.0;
+
+/// How monsters chasing the player find their next step
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChasePathing {
+    /// Everyone walks down one chase map built once a turn. What the game uses.
+    Shared,
+    /// Every monster runs its own A* search every turn, as before the chase map. Kept so the benchmark can compare.
+    PerMonster
+}
+
+pub struct MonsterAI {
+    pub pathing : ChasePathing
+}
+
+/// Steps downhill on the shared chase map, if a neighbouring tile can still reach the player
+fn downhill(chase : &DijkstraMap, map : &Map, idx : usize) -> Option<usize> {
+    map.get_available_exits(idx).iter()
+        .filter(|(exit, _)| chase.map[*exit] < f32::MAX)
+        .min_by(|a, b| chase.map[a.0].partial_cmp(&chase.map[b.0]).unwrap())
+        .map(|(exit, _)| *exit)
+}
+
+/// First step of a fresh A* search, with nothing shared or remembered
+fn searched_step(map : &Map, here : usize, goal : usize) -> Option<usize> {
+    let path = rltk::a_star_search(here, goal, map);
+    if path.success && path.steps.len() > 1 {Some(path.steps[1])} else {None}
+}
+
+/// Next step towards a goal other than the player, reusing the path from earlier turns while it stays clear
+fn cached_step(cache : &mut WriteStorage<PathCache>, entity : Entity, map : &Map, here : usize, goal : usize) -> Option<usize> {
+    let w = map.width as usize;
+    let adjacent = |a : usize, b : usize| (a % w).abs_diff(b % w) <= 1 && (a / w).abs_diff(b / w) <= 1;
+    let still_good = cache.get(entity).is_some_and(|path| {
+        path.goal == goal && path.steps.last().is_some_and(|next| !map.blocked[*next] && adjacent(here, *next))
+    });
+
+    if !still_good {
+        cache.remove(entity);
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(
+            Point::new(here % w, here / w), Point::new(goal % w, goal / w));
+        if distance > MAX_PATH_DISTANCE {return None;}
+
+        let path = rltk::a_star_search(here, goal, map);
+        if !path.success || path.steps.len() < 2 {return None;}
+        let steps = path.steps[1..].iter().rev().cloned().collect();
+        cache.insert(entity, PathCache{goal, steps}).expect("Unable to insert path");
+    }
+
+    cache.get_mut(entity).and_then(|path| path.steps.pop())
+}
+
+/// How much an equippable item improves its wearer
+fn item_score(item : Entity, power : &ReadStorage<MeleePowerBonus>, defense : &ReadStorage<DefenseBonus>) -> i32 {
+    power.get(item).map_or(0, |bonus| bonus.power) + defense.get(item).map_or(0, |bonus| bonus.defense)
+}
 
 impl<'a> System<'a> for MonsterAI {
     #[allow(clippy::type_complexity)]
@@ -10,38 +73,313 @@ impl<'a> System<'a> for MonsterAI {
                         ReadExpect<'a, Point>,
                         ReadExpect<'a, Entity>,
                         ReadExpect<'a, RunState>,
+                        WriteExpect<'a, Gamelog>,
                         Entities<'a>,
                         WriteStorage<'a, Fov>,
                         ReadStorage<'a, Monster>,
                         WriteStorage<'a, Position>,
-                        WriteStorage<'a, AttackIntent>);
+                        WriteStorage<'a, AttackIntent>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, Abilities>,
+                        WriteStorage<'a, WantsToUseAbility>,
+                        ReadStorage<'a, Pack>,
+                        WriteStorage<'a, Alerted>,
+                        ReadStorage<'a, Name>,
+                        ( ReadStorage<'a, Item>,
+                          ReadStorage<'a, InBackpack>,
+                          ReadStorage<'a, Equipped>,
+                          ReadStorage<'a, Equippable>,
+                          ReadStorage<'a, Potion>,
+                          ReadStorage<'a, MeleePowerBonus>,
+                          ReadStorage<'a, DefenseBonus>,
+                          ReadStorage<'a, UsesItems>,
+                          WriteStorage<'a, WantsToPickupItem>,
+                          WriteStorage<'a, WantsToDrinkPotion>,
+                          ReadStorage<'a, Wand>,
+                          ReadStorage<'a, Bolt>,
+                          ReadStorage<'a, Ranged>,
+                          WriteStorage<'a, WantsToZap>,
+                          ReadStorage<'a, CurseStatus> ),
+                        WriteStorage<'a, Awareness>,
+                        WriteExpect<'a, NoiseEvents>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteStorage<'a, PathCache>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteStorage<'a, Slowed>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, player_pos, player_entity, runstate, entities, mut fov, monster, mut position, mut attack_intent) = data;
+        let (mut map, player_pos, player_entity, runstate, mut log, entities, mut fov, monster, mut position,
+            mut attack_intent, combat_stats, mut abilities, mut wants_ability, packs, mut alerted, names, item_data,
+            mut awareness, mut noise, mut rng, mut path_cache, mut particles, mut slowed) = data;
+        let (items, backpack, equipped, equippable, potions, power_bonus, defense_bonus, uses_items,
+            mut wants_pickup, mut wants_drink, wands, bolts, ranged, mut wants_zap, curses) = item_data;
 
         if *runstate != RunState::MonsterTurn {return;}
 
-        for (entity, mut fov, _monster, mut pos) in (&entities, &mut fov, &monster, &mut position).join() {
-            let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
+        // Wounded monsters that a healer could patch up, with how much health they have left
+        let wounded : Vec<(Entity, Point, f32)> = (&entities, &monster, &position, &combat_stats).join()
+            .filter(|(_e, _m, _p, stats)| stats.hp > 0 && stats.hp < stats.max_hp)
+            .map(|(e, _m, p, stats)| (e, Point::new(p.x, p.y), stats.hp as f32 / stats.max_hp as f32))
+            .collect();
+
+        // A pack member that spots the player warns the rest of its pack
+        let mut pack_alerts : HashMap<u32, Point> = HashMap::new();
+        for (entity, fov, pack, pos) in (&entities, &fov, &packs, &position).join() {
+            let awake = awareness.get(entity).is_none_or(|aware| aware.state == AwarenessState::Aware);
+            if !awake || !fov.can_see(map.xy_idx(player_pos.x, player_pos.y)) || pack_alerts.contains_key(&pack.id) {continue;}
+            pack_alerts.insert(pack.id, *player_pos);
+
+            let unaware = (&entities, &packs).join()
+                .any(|(other, other_pack)| other_pack.id == pack.id && other != entity && alerted.get(other).is_none());
+            if unaware {
+                noise.emit(pos.x, pos.y, SHOUT_NOISE);
+                particles.ring(Point::new(pos.x, pos.y), 3, RGB::named(rltk::YELLOW));
+                if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                    log.entries.push(format!("{} shouts a warning to its pack!", names.get(entity).unwrap().name));
+                }
+            }
+        }
+
+        // Packs that already have a member fighting in a corridor
+        let mut in_corridor : HashSet<u32> = (&packs, &position).join()
+            .filter(|(_pack, pos)| map.is_corridor(map.xy_idx(pos.x, pos.y)))
+            .map(|(pack, _pos)| pack.id)
+            .collect();
+        let mut claimed : HashSet<usize> = HashSet::new();
+
+        // Items lying on the floor that monsters might go after
+        let floor_items : Vec<(Entity, Point)> = (&entities, &items, &position).join()
+            .map(|(e, _i, p)| (e, Point::new(p.x, p.y)))
+            .collect();
+        let mut claimed_items : HashSet<Entity> = HashSet::new();
+
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+        let mut chase_map : Option<DijkstraMap> = None;
+
+        for (entity, fov, _monster, pos, mut abilities, pack) in (&entities, &mut fov, &monster, &mut position, (&mut abilities).maybe(), packs.maybe()).join() {
+            let here = Point::new(pos.x, pos.y);
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(here, *player_pos);
+
+            // Slowed monsters only act every other turn
+            if let Some(slow) = slowed.get_mut(entity) {
+                slow.turns -= 1;
+                let lagging = slow.turns % 2 == 1;
+                if slow.turns <= 0 {
+                    slowed.remove(entity);
+                }
+                if lagging {continue;}
+            }
+
+            // Monsters far from the player sit still unless they are hunting
+            if distance > ACTIVE_RADIUS && alerted.get(entity).is_none() {continue;}
+            let sees_player = fov.can_see(player_idx);
+
+            // Sleepers do nothing, and the unaware may still fail to notice the player
+            if let Some(awareness) = awareness.get_mut(entity) {
+                match awareness.state {
+                    AwarenessState::Asleep => continue,
+                    AwarenessState::Unaware => {
+                        if !sees_player || rng.roll_dice(1, 10) as f32 <= distance {continue;}
+                        awareness.state = AwarenessState::Aware;
+                    }
+                    AwarenessState::Aware => {}
+                }
+            }
+
+            if let Some(abilities) = &mut abilities {
+                for ability in abilities.list.iter_mut() {
+                    ability.turns_until_ready = i32::max(0, ability.turns_until_ready - 1);
+                }
+            }
+
+            if sees_player {
+                alerted.insert(entity, Alerted{last_seen: *player_pos}).expect("Unable to insert alert");
+            } else if let Some(last_seen) = pack.and_then(|pack| pack_alerts.get(&pack.id)) {
+                alerted.insert(entity, Alerted{last_seen: *last_seen}).expect("Unable to insert alert");
+            }
+
+            // What this monster carries and wears, if it bothers with items at all
+            let mut carried : Vec<Entity> = Vec::new();
+            let mut worn : Vec<Entity> = Vec::new();
+            if uses_items.get(entity).is_some() {
+                carried = (&entities, &backpack).join().filter(|(_, pack)| pack.owner == entity).map(|(e, _)| e).collect();
+                worn = (&entities, &equipped).join().filter(|(_, eq)| eq.owner == entity).map(|(e, _)| e).collect();
+            }
+
+            // Drink a potion when badly hurt
+            let hurt = combat_stats.get(entity).is_some_and(|stats| stats.hp * 2 <= stats.max_hp);
+            if let Some(potion) = carried.iter().find(|item| potions.get(**item).is_some()) {
+                if hurt {
+                    wants_drink.insert(entity, WantsToDrinkPotion{potion: *potion}).expect("Unable to insert drink");
+                    continue;
+                }
+            }
+
             if distance < 1.5 {
                 attack_intent.insert(entity, AttackIntent{target: *player_entity}).expect("Unable to insert attack");
+                continue;
             }
-            else if fov.visible_tiles.contains(&*player_pos) {
-                // Path to the Player
-                let path = rltk::a_star_search(
-                    map.xy_idx(pos.x, pos.y),
-                    map.xy_idx(player_pos.x, player_pos.y),
-                    &mut *map
-                );
-                if path.success && path.steps.len() > 1 {
-                    let mut idx = map.xy_idx(pos.x, pos.y);
-                    map.blocked[idx] = false;
-                    pos.x = path.steps[1] as i32 % map.width;
-                    pos.y = path.steps[1] as i32 / map.width;
-                    idx = map.xy_idx(pos.x, pos.y);
-                    map.blocked[idx] = true;
-                    fov.dirty = true;
+
+            // Abilities need a clear line of sight and the target within range
+            if let Some(abilities) = abilities {
+                let mut chosen : Option<(usize, Entity)> = None;
+                for (i, ability) in abilities.list.iter().enumerate() {
+                    if ability.turns_until_ready > 0 {continue;}
+                    let in_reach = |target : Point| {
+                        fov.can_see(map.xy_idx(target.x, target.y)) &&
+                            rltk::DistanceAlg::Pythagoras.distance2d(here, target) <= ability.range
+                    };
+                    match ability.effect {
+                        AbilityEffect::Damage{..} => {
+                            if in_reach(*player_pos) {chosen = Some((i, *player_entity));}
+                        }
+                        AbilityEffect::HealAlly{..} => {
+                            // The worst hurt ally in reach, never the healer itself
+                            let patient = wounded.iter()
+                                .filter(|(ally, ally_pos, _health)| *ally != entity && in_reach(*ally_pos))
+                                .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+                            if let Some((ally, ..)) = patient {
+                                chosen = Some((i, *ally));
+                            }
+                        }
+                    }
+                    if chosen.is_some() {break;}
                 }
+
+                if let Some((i, target)) = chosen {
+                    abilities.list[i].turns_until_ready = abilities.list[i].cooldown;
+                    wants_ability.insert(entity, WantsToUseAbility{ability: i, target}).expect("Unable to insert ability");
+                    continue;
+                }
+            }
+
+            // Zap a charged wand at the player in sight and range. Digging wands are no use in a fight.
+            let zappable = carried.iter().find(|item| {
+                wands.get(**item).is_some_and(|wand| wand.charges > 0)
+                    && bolts.get(**item).is_some_and(|bolt| !matches!(bolt.effect, BoltEffect::Dig))
+                    && ranged.get(**item).is_some_and(|ranged| distance <= ranged.range as f32)
+            });
+            if let (true, Some(wand)) = (sees_player, zappable) {
+                wants_zap.insert(entity, WantsToZap{wand: *wand, target: *player_pos}).expect("Unable to insert zap");
+                continue;
+            }
+
+            // Swap in carried equipment that beats what is worn in the same slot
+            let best_in_slot = |slot, among : &Vec<Entity>| among.iter()
+                .filter(|item| equippable.get(**item).is_some_and(|eq| eq.slot == slot))
+                .map(|item| item_score(*item, &power_bonus, &defense_bonus))
+                .max()
+                .unwrap_or(0);
+            // Nothing replaces cursed gear
+            let stuck = |slot| worn.iter().any(|item| equippable.get(*item).is_some_and(|eq| eq.slot == slot)
+                && curses.get(*item).is_some_and(|curse| curse.blessing == Blessing::Cursed));
+            let upgrade = carried.iter().find(|item| equippable.get(**item).is_some_and(|eq| {
+                !stuck(eq.slot) && item_score(**item, &power_bonus, &defense_bonus) > best_in_slot(eq.slot, &worn)
+            }));
+            if let Some(upgrade) = upgrade {
+                wants_drink.insert(entity, WantsToDrinkPotion{potion: *upgrade}).expect("Unable to insert equip");
+                continue;
+            }
+
+            // Head for the player, or for where the pack last saw them
+            let goal = if let Some(alert) = alerted.get(entity) {
+                if !sees_player && alert.last_seen == here {
+                    alerted.remove(entity);
+                    continue;
+                }
+                alert.last_seen
+            } else if uses_items.get(entity).is_some() {
+                // Otherwise wander over to the nearest visible item worth having
+                let potion_count = carried.iter().filter(|item| potions.get(**item).is_some()).count();
+                let wanted = floor_items.iter()
+                    .filter(|(item, item_pos)| !claimed_items.contains(item) && fov.can_see(map.xy_idx(item_pos.x, item_pos.y)))
+                    .filter(|(item, _)| {
+                        if potions.get(*item).is_some() {return potion_count < 2;}
+                        if wands.get(*item).is_some_and(|wand| wand.charges > 0) {return true;}
+                        equippable.get(*item).is_some_and(|eq| {
+                            let owned = carried.iter().chain(worn.iter()).cloned().collect();
+                            !stuck(eq.slot) && item_score(*item, &power_bonus, &defense_bonus) > best_in_slot(eq.slot, &owned)
+                        })
+                    })
+                    .min_by(|a, b| {
+                        let dist_a = rltk::DistanceAlg::Pythagoras.distance2d(here, a.1);
+                        let dist_b = rltk::DistanceAlg::Pythagoras.distance2d(here, b.1);
+                        dist_a.partial_cmp(&dist_b).unwrap()
+                    });
+                match wanted {
+                    None => continue,
+                    Some((item, item_pos)) => {
+                        claimed_items.insert(*item);
+                        if *item_pos == here {
+                            wants_pickup.insert(entity, WantsToPickupItem{collected_by: entity, item: *item}).expect("Unable to insert pickup");
+                            continue;
+                        }
+                        *item_pos
+                    }
+                }
+            } else {
+                continue;
+            };
+
+            let here_idx = map.xy_idx(pos.x, pos.y);
+            let goal_idx = map.xy_idx(goal.x, goal.y);
+            let step = if goal_idx == player_idx {
+                let mut step = match self.pathing {
+                    ChasePathing::Shared => {
+                        // Everyone after the player shares one bounded chase map, built at most once a turn
+                        let chase = chase_map.get_or_insert_with(|| {
+                            DijkstraMap::new(map.width, map.height, &[player_idx], &*map, MAX_CHASE_DEPTH)
+                        });
+                        downhill(chase, &map, here_idx)
+                    }
+                    ChasePathing::PerMonster => searched_step(&map, here_idx, player_idx)
+                };
+
+                if sees_player && pack.is_some() {
+                    // Spread out around the player instead of queueing behind each other
+                    let mut open_sides : Vec<(f32, Point)> = Vec::new();
+                    for dy in -1 ..= 1 {
+                        for dx in -1 ..= 1 {
+                            let side = Point::new(player_pos.x + dx, player_pos.y + dy);
+                            if side.x < 1 || side.x > map.width-1 || side.y < 1 || side.y > map.height-1 {continue;}
+                            let idx = map.xy_idx(side.x, side.y);
+                            if map.blocked[idx] || claimed.contains(&idx) {continue;}
+                            open_sides.push((rltk::DistanceAlg::Pythagoras.distance2d(here, side), side));
+                        }
+                    }
+                    open_sides.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                    if let Some((side_distance, side)) = open_sides.first() {
+                        claimed.insert(map.xy_idx(side.x, side.y));
+                        if *side_distance < 3.0 {
+                            let w = map.width;
+                            step = map.get_available_exits(here_idx).iter()
+                                .map(|(exit, _)| *exit)
+                                .min_by(|a, b| {
+                                    let dist_a = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(*a as i32 % w, *a as i32 / w), *side);
+                                    let dist_b = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(*b as i32 % w, *b as i32 / w), *side);
+                                    dist_a.partial_cmp(&dist_b).unwrap()
+                                });
+                        }
+                    }
+                }
+                step
+            } else {
+                cached_step(&mut path_cache, entity, &map, here_idx, goal_idx)
+            };
+
+            if let Some(step) = step {
+                // Hold the corridor mouth while a packmate is already fighting inside
+                if let Some(pack) = pack {
+                    let entering = map.is_corridor(step) && !map.is_corridor(here_idx);
+                    if sees_player && entering && in_corridor.contains(&pack.id) {continue;}
+                    if map.is_corridor(step) {in_corridor.insert(pack.id);}
+                }
+
+                map.blocked[here_idx] = false;
+                pos.x = step as i32 % map.width;
+                pos.y = step as i32 / map.width;
+                map.blocked[step] = true;
+                fov.dirty = true;
             }
         }
     }
@@ -0,0 +1,68 @@
+use specs::prelude::*;
+use super::{Awareness, AwarenessState, Alerted, Position, Name, Map, gamelog::Gamelog};
+use rltk::Point;
+
+pub const FOOTSTEP_NOISE : f32 = 3.0;
+pub const COMBAT_NOISE : f32 = 7.0;
+pub const SHOUT_NOISE : f32 = 10.0;
+
+pub struct Noise {
+    pub origin : Point,
+    pub radius : f32
+}
+
+/// Noises made since the last time monsters listened
+#[derive(Default)]
+pub struct NoiseEvents {
+    pub noises : Vec<Noise>
+}
+
+impl NoiseEvents {
+    pub fn emit(&mut self, x : i32, y : i32, radius : f32) {
+        self.noises.push(Noise{origin: Point::new(x, y), radius});
+    }
+}
+
+pub struct NoiseSystem {}
+
+impl<'a> System<'a> for NoiseSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, NoiseEvents>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteStorage<'a, Awareness>,
+                        WriteStorage<'a, Alerted>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, map, mut events, mut log, mut awareness, mut alerted, positions, names) = data;
+
+        for (entity, awareness, pos, name) in (&entities, &mut awareness, &positions, &names).join() {
+            let here = Point::new(pos.x, pos.y);
+            let heard = events.noises.iter()
+                .find(|noise| rltk::DistanceAlg::Pythagoras.distance2d(here, noise.origin) <= noise.radius);
+
+            if let Some(noise) = heard {
+                match awareness.state {
+                    AwarenessState::Asleep => {
+                        // Sleepers only stir; they still have to notice the player
+                        awareness.state = AwarenessState::Unaware;
+                        if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                            log.entries.push(format!("{} wakes up.", name.name));
+                        }
+                    }
+                    AwarenessState::Unaware => {
+                        // Go and see what made the noise
+                        awareness.state = AwarenessState::Aware;
+                        alerted.insert(entity, Alerted{last_seen: noise.origin}).expect("Unable to insert alert");
+                    }
+                    AwarenessState::Aware => {}
+                }
+            }
+        }
+
+        events.noises.clear();
+    }
+}
\ No newline at end of file
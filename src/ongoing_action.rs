@@ -0,0 +1,103 @@
+use specs::prelude::*;
+use super::{OngoingAction, ActionKind, CombatStats, HungerClock, Experience, RunState, gamelog::Gamelog,
+    player::{skip_turn, monster_in_view, regen_rate}};
+
+/// Longest any action may run before it stops by itself
+const MAX_ACTION_TURNS : i32 = 200;
+
+/// The player's HP, hunger and level, compared turn to turn to spot interruptions
+fn snapshot(ecs : &World) -> (i32, Option<super::HungerState>, i32) {
+    let player_entity = ecs.fetch::<Entity>();
+    let hp = ecs.read_storage::<CombatStats>().get(*player_entity).map_or(0, |stats| stats.hp);
+    let hunger = ecs.read_storage::<HungerClock>().get(*player_entity).map(|clock| clock.state);
+    let level = ecs.read_storage::<Experience>().get(*player_entity).map_or(1, |exp| exp.level);
+    (hp, hunger, level)
+}
+
+/// Why an action cannot start or carry on, or None if it is free to
+fn blocked(ecs : &World, kind : ActionKind) -> Option<&'static str> {
+    if monster_in_view(ecs) {
+        return Some("A monster comes into view!");
+    }
+    match kind {
+        ActionKind::Rest => {
+            let player_entity = ecs.fetch::<Entity>();
+            let healed = ecs.read_storage::<CombatStats>().get(*player_entity).is_none_or(|stats| stats.hp >= stats.max_hp);
+            if healed {
+                Some("You feel fully rested.")
+            } else if regen_rate(ecs) == 0 {
+                Some("You are too hungry to rest.")
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Plays one turn of the action
+fn step(ecs : &mut World, kind : ActionKind) -> RunState {
+    match kind {
+        ActionKind::Rest => skip_turn(ecs)
+    }
+}
+
+/// Plays one turn of the action, then remembers how the player stands afterwards. Snapshotting
+/// after the turn keeps resting's own healing from hiding damage taken before the next one.
+fn take_turn(ecs : &mut World, kind : ActionKind, turns_left : i32) -> RunState {
+    let state = step(ecs, kind);
+    let (last_hp, last_hunger, last_level) = snapshot(ecs);
+    let player_entity = *ecs.fetch::<Entity>();
+    ecs.write_storage::<OngoingAction>()
+        .insert(player_entity, OngoingAction{kind, turns_left, last_hp, last_hunger, last_level})
+        .expect("Unable to insert action");
+    state
+}
+
+fn log(ecs : &World, message : &str) {
+    ecs.fetch_mut::<Gamelog>().entries.push(message.to_string());
+}
+
+/// Begins a multi-turn action, taking its first turn straight away
+pub fn start(ecs : &mut World, kind : ActionKind) -> RunState {
+    if let Some(reason) = blocked(ecs, kind) {
+        log(ecs, reason);
+        return RunState::AwaitingInput;
+    }
+
+    if kind == ActionKind::Rest {
+        log(ecs, "You settle down to rest.");
+    }
+    take_turn(ecs, kind, MAX_ACTION_TURNS)
+}
+
+/// Takes the next turn of the player's ongoing action, if there is one and nothing has interrupted it.
+/// Any key press cancels it, and is used up doing so. Returns None when the player should be asked for input.
+pub fn resume(ecs : &mut World, key_pressed : bool) -> Option<RunState> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let action = ecs.read_storage::<OngoingAction>().get(player_entity).cloned()?;
+
+    if key_pressed {
+        ecs.write_storage::<OngoingAction>().remove(player_entity);
+        log(ecs, "You stop.");
+        return Some(RunState::AwaitingInput);
+    }
+
+    let (hp, hunger, level) = snapshot(ecs);
+    let interruption = if hp < action.last_hp {
+        Some("You are hurt!")
+    } else if hunger != action.last_hunger || level != action.last_level {
+        Some("You stop to take stock.")
+    } else if action.turns_left <= 0 {
+        Some("You give up after a long while.")
+    } else {
+        blocked(ecs, action.kind)
+    };
+
+    if let Some(reason) = interruption {
+        ecs.write_storage::<OngoingAction>().remove(player_entity);
+        log(ecs, reason);
+        return None;
+    }
+
+    Some(take_turn(ecs, action.kind, action.turns_left - 1))
+}
\ No newline at end of file
@@ -0,0 +1,117 @@
+use specs::prelude::*;
+use rltk::{Rltk, RGB, Point, LineAlg};
+use super::{Position, Renderable, ParticleLifetime, Map};
+
+/// How long a hit flash stays on screen
+const FLASH_MS : f32 = 200.0;
+/// How long each step of a projectile trail lingers, and the gap before the next appears
+const TRAIL_MS : f32 = 100.0;
+const TRAIL_STEP_MS : f32 = 25.0;
+/// Gap between the rings of an area effect as it spreads out
+const RING_STEP_MS : f32 = 60.0;
+
+struct ParticleRequest {
+    x : i32,
+    y : i32,
+    fg : RGB,
+    bg : RGB,
+    glyph : rltk::FontCharType,
+    delay_ms : f32,
+    lifetime_ms : f32
+}
+
+/// Queue of particles for ParticleSpawnSystem to create, filled by whichever system wants an effect
+#[derive(Default)]
+pub struct ParticleBuilder {
+    requests : Vec<ParticleRequest>
+}
+
+impl ParticleBuilder {
+    #[allow(clippy::too_many_arguments)]
+    pub fn request(&mut self, x : i32, y : i32, fg : RGB, bg : RGB, glyph : rltk::FontCharType, delay_ms : f32, lifetime_ms : f32) {
+        self.requests.push(ParticleRequest{x, y, fg, bg, glyph, delay_ms, lifetime_ms});
+    }
+
+    /// A flash on the tile of something that was hit
+    pub fn flash(&mut self, x : i32, y : i32, fg : RGB) {
+        self.request(x, y, fg, RGB::named(rltk::BLACK), rltk::to_cp437('‼'), 0.0, FLASH_MS);
+    }
+
+    /// A projectile flying from one tile to another, one step at a time
+    pub fn trail(&mut self, from : Point, to : Point, fg : RGB, glyph : rltk::FontCharType) {
+        let path = rltk::line2d(LineAlg::Bresenham, from, to);
+        for (step, point) in path.iter().enumerate().skip(1) {
+            self.request(point.x, point.y, fg, RGB::named(rltk::BLACK), glyph, step as f32 * TRAIL_STEP_MS, TRAIL_MS);
+        }
+    }
+
+    /// Rings spreading out from a point, one per tile of radius
+    pub fn ring(&mut self, center : Point, radius : i32, fg : RGB) {
+        for r in 1..=radius {
+            for x in center.x - r ..= center.x + r {
+                for y in center.y - r ..= center.y + r {
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(center, Point::new(x, y));
+                    if (distance - r as f32).abs() < 0.5 {
+                        self.request(x, y, fg, RGB::named(rltk::BLACK), rltk::to_cp437('°'), r as f32 * RING_STEP_MS, RING_STEP_MS * 2.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Turns queued requests into particle entities, dropping any the player could not see
+pub struct ParticleSpawnSystem {}
+
+impl<'a> System<'a> for ParticleSpawnSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, Renderable>,
+                        WriteStorage<'a, ParticleLifetime>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, map, mut particles, mut positions, mut renderables, mut lifetimes) = data;
+
+        for request in particles.requests.drain(..) {
+            if request.x < 0 || request.x >= map.width || request.y < 0 || request.y >= map.height {continue;}
+            if !map.visible_tiles[map.xy_idx(request.x, request.y)] {continue;}
+
+            let particle = entities.create();
+            positions.insert(particle, Position{x: request.x, y: request.y}).expect("Unable to insert position");
+            renderables.insert(particle, Renderable{
+                glyph: request.glyph,
+                fg: request.fg,
+                bg: request.bg,
+                render_order: 0
+            }).expect("Unable to insert renderable");
+            lifetimes.insert(particle, ParticleLifetime{delay_ms: request.delay_ms, lifetime_ms: request.lifetime_ms})
+                .expect("Unable to insert lifetime");
+        }
+    }
+}
+
+/// Ages particles by the frame time, deleting the ones that have run out
+pub fn cull_dead_particles(ecs : &mut World, ctx : &Rltk) {
+    let dead : Vec<Entity> = {
+        let entities = ecs.entities();
+        let mut lifetimes = ecs.write_storage::<ParticleLifetime>();
+        let mut dead = Vec::new();
+        for (entity, particle) in (&entities, &mut lifetimes).join() {
+            if particle.delay_ms > 0.0 {
+                particle.delay_ms -= ctx.frame_time_ms;
+            } else {
+                particle.lifetime_ms -= ctx.frame_time_ms;
+                if particle.lifetime_ms < 0.0 {
+                    dead.push(entity);
+                }
+            }
+        }
+        dead
+    };
+    for particle in dead {
+        ecs.delete_entity(particle).expect("Unable to delete particle");
+    }
+}
\ No newline at end of file
@@ -1,40 +1,121 @@
 use rltk::{VirtualKeyCode, Rltk, Point};
 use specs::prelude::*;
-use std::cmp::{min, max};
+use specs::hibitset::BitSetLike;
 use super::{Position, Player, Fov, State, Map, RunState, CombatStats, AttackIntent,
-	Item, gamelog::Gamelog, WantsToPickupItem, TileType, Monster};
+	Item, gamelog::Gamelog, WantsToPickupItem, TileType, Monster, noise_system::{NoiseEvents, FOOTSTEP_NOISE},
+	Boss, Name, Friendly, ForcedMove, BlocksTile, HungerClock, HungerState, ActionKind, ongoing_action, Encumbrance, Slowed, Container, containers, Merchant};
+
+/// Moves the player, attacking whatever blocks the way, or shoving it when `shove` is held.
+/// Bumping into a container opens it and bumping into a merchant trades, or with `shove` asks before attacking.
+pub fn player_move(dx: i32, dy: i32, shove: bool, ecs: &mut World) -> RunState {
+	let (chest, merchant) = {
+		let player_pos = ecs.fetch::<Point>();
+		let map = ecs.fetch::<Map>();
+		let containers = ecs.read_storage::<Container>();
+		let merchants = ecs.read_storage::<Merchant>();
+		let (x, y) = (player_pos.x + dx, player_pos.y + dy);
+		if x < 0 || x >= map.width || y < 0 || y >= map.height {
+			(None, None)
+		} else {
+			let here = &map.tile_content[map.xy_idx(x, y)];
+			(here.iter().copied().find(|entity| containers.get(*entity).is_some()),
+				here.iter().copied().find(|entity| merchants.get(*entity).is_some()))
+		}
+	};
+	if let Some(chest) = chest {
+		return containers::open(ecs, chest);
+	}
+	if let Some(merchant) = merchant {
+		return if shove {RunState::ConfirmAttack{target: merchant}} else {RunState::ShowShop{merchant, selling: false, page: 0}};
+	}
 
-pub fn player_move(dx: i32, dy: i32, ecs: &mut World) {
 	let mut pos = ecs.write_storage::<Position>();
 	let mut players = ecs.write_storage::<Player>();
 	let mut fovs = ecs.write_storage::<Fov>();
-	let map = ecs.fetch::<Map>();
+	let mut map = ecs.fetch_mut::<Map>();
 	let combat_stats = ecs.read_storage::<CombatStats>();
 	let entities = ecs.entities();
 	let mut attack_intent = ecs.write_storage::<AttackIntent>();
+	let friendlies = ecs.read_storage::<Friendly>();
+	let bosses = ecs.read_storage::<Boss>();
+	let names = ecs.read_storage::<Name>();
+	let blockers = ecs.read_storage::<BlocksTile>();
+	let mut forced_moves = ecs.write_storage::<ForcedMove>();
+	let mut encumbrance = ecs.write_storage::<Encumbrance>();
+	let mut swapped : Option<(Entity, i32, i32)> = None;
 
 	for (entity,_player, p, fov) in (&entities, &mut players, &mut pos, &mut fovs).join() {
-		if p.x + dx < 1 || p.x + dx > map.width-1 || p.y + dy < 1 || p.y + dy > map.height-1 {return;}
+		if p.x + dx < 1 || p.x + dx > map.width-1 || p.y + dy < 1 || p.y + dy > map.height-1 {return RunState::PlayerTurn;}
 		let dest_idx = map.xy_idx(p.x + dx, p.y + dy);
 
+		let mut swap_with = None;
 		for potential_target in map.tile_content[dest_idx].iter() {
+			if friendlies.get(*potential_target).is_some() {
+				swap_with = Some(*potential_target);
+				break;
+			}
 			let target = combat_stats.get(*potential_target);
 			if let Some(_target) = target {
-				attack_intent.insert(entity, AttackIntent{target: *potential_target}).expect("Add target failed");
-				return; // For not moving after attacking
+				let mut gamelog = ecs.fetch_mut::<Gamelog>();
+				let target_name = names.get(*potential_target).map_or("it", |n| n.name.as_str());
+				if !shove {
+					attack_intent.insert(entity, AttackIntent{target: *potential_target}).expect("Add target failed");
+				} else if bosses.get(*potential_target).is_some() {
+					gamelog.entries.push(format!("{} does not budge.", target_name));
+				} else {
+					forced_moves.insert(*potential_target, ForcedMove{dx, dy, distance: 1, source: Some(entity)})
+						.expect("Unable to insert forced move");
+					gamelog.entries.push(format!("You shove the {}.", target_name));
+				}
+				return RunState::PlayerTurn; // For not moving after attacking
 			}
 		}
 
+		// Trading places with a friendly creature
+		if let Some(friend) = swap_with {
+			let here = map.xy_idx(p.x, p.y);
+			map.move_entity(friend, dest_idx, here, blockers.get(friend).is_some());
+			map.move_entity(entity, here, dest_idx, false);
+			swapped = Some((friend, p.x, p.y));
+			p.x += dx;
+			p.y += dy;
+			fov.dirty = true;
+			let mut ppos = ecs.write_resource::<Point>();
+			ppos.x = p.x;
+			ppos.y = p.y;
+			continue;
+		}
+
 		if !map.blocked[dest_idx] {
-			p.x = min(79, max(0, p.x + dx));
-			p.y = min(49, max(0, p.y + dy));
+			p.x = (p.x + dx).clamp(0, 79);
+			p.y = (p.y + dy).clamp(0, 49);
+			if let Some(load) = encumbrance.get_mut(entity) {
+				load.lagging = load.overburdened();
+			}
 
 			fov.dirty = true;
 			let mut ppos = ecs.write_resource::<Point>();
 			ppos.x = p.x;
 			ppos.y = p.y;
+
+			let mut noise = ecs.write_resource::<NoiseEvents>();
+			noise.emit(p.x, p.y, FOOTSTEP_NOISE);
+
+			if map.reveal_hidden_stairs(p.x, p.y) {
+				ecs.fetch_mut::<Gamelog>().entries.push("You find a hidden passage leading down!".to_string());
+			}
+		}
+	}
+
+	if let Some((friend, x, y)) = swapped {
+		if let Some(friend_pos) = pos.get_mut(friend) {
+			friend_pos.x = x;
+			friend_pos.y = y;
 		}
+		let name = names.get(friend).map_or("them", |n| n.name.as_str());
+		ecs.fetch_mut::<Gamelog>().entries.push(format!("You swap places with {}.", name));
 	}
+	RunState::PlayerTurn
 }
 
 fn get_item(ecs: &mut World) {
@@ -66,6 +147,16 @@ pub fn climb_down(ecs: &mut World) -> bool {
 	let map = ecs.fetch::<Map>();
 	let player_idx = map.xy_idx(player_pos.x, player_pos.y);
 	if map.tiles[player_idx] == TileType::DownStairs {
+		// The lair's stairs stay sealed while its boss lives
+		if map.in_boss_room(player_pos.x, player_pos.y) {
+			let bosses = ecs.read_storage::<Boss>();
+			let names = ecs.read_storage::<Name>();
+			if let Some((_boss, name)) = (&bosses, &names).join().next() {
+				let mut gamelog = ecs.fetch_mut::<Gamelog>();
+				gamelog.entries.push(format!("The way down is sealed while {} lives.", name.name));
+				return false;
+			}
+		}
 		true
 	} else {
 		let mut gamelog = ecs.fetch_mut::<Gamelog>();
@@ -74,31 +165,54 @@ pub fn climb_down(ecs: &mut World) -> bool {
 	}
 }
 
-/// Heals if monsters can't be seen, otherwise would have just returned RunState::PlayerTurn
-fn skip_turn(ecs: &mut World) -> RunState {
+/// Whether any monster stands on a tile the player can see
+pub fn monster_in_view(ecs: &World) -> bool {
 	let player_entity = ecs.fetch::<Entity>();
 	let sight = ecs.read_storage::<Fov>();
 	let monsters = ecs.read_storage::<Monster>();
-
 	let map = ecs.fetch::<Map>();
 
-	let mut can_heal = true;
 	let fov = sight.get(*player_entity).unwrap();
-	for tile in fov.visible_tiles.iter() {
-		let idx = map.xy_idx(tile.x, tile.y);
-		for entity in map.tile_content[idx].iter() {
-			let mob = monsters.get(*entity);
-			match mob {
-				None => {}
-				Some(_) => can_heal = false
-			}
-		}
+	(&fov.visible).iter().any(|idx| {
+		map.tile_content[idx as usize].iter().any(|entity| monsters.get(*entity).is_some())
+	})
+}
+
+/// HP the player regains on a quiet turn, which depends on how well fed they are
+pub fn regen_rate(ecs: &World) -> i32 {
+	let player_entity = ecs.fetch::<Entity>();
+	let hunger = ecs.read_storage::<HungerClock>();
+	match hunger.get(*player_entity).map(|clock| clock.state) {
+		Some(HungerState::WellFed) => 2,
+		Some(HungerState::Hungry) | Some(HungerState::Starving) => 0,
+		_ => 1
+	}
+}
+
+/// Counts down a slowed player's slowness, true on the turns monsters get to act twice
+pub fn slowed_lags(ecs: &mut World) -> bool {
+	let player_entity = ecs.fetch::<Entity>();
+	let mut slowed = ecs.write_storage::<Slowed>();
+	let Some(slow) = slowed.get_mut(*player_entity) else {return false};
+	slow.turns -= 1;
+	let lags = slow.turns % 2 == 1;
+	if slow.turns <= 0 {
+		slowed.remove(*player_entity);
+		ecs.fetch_mut::<Gamelog>().entries.push("You feel yourself speed up.".to_string());
 	}
+	lags
+}
+
+/// Heals if monsters can't be seen, otherwise would have just returned RunState::PlayerTurn
+pub fn skip_turn(ecs: &mut World) -> RunState {
+	let player_entity = ecs.fetch::<Entity>();
+	let can_heal = !monster_in_view(ecs);
+	let regen = regen_rate(ecs);
 
-	if can_heal {
+	if can_heal && regen > 0 {
 		let mut health = ecs.write_storage::<CombatStats>();
 		let player_hp = health.get_mut(*player_entity).unwrap();
-		player_hp.hp = i32::min(player_hp.hp + 1, player_hp.max_hp);
+		player_hp.hp = i32::min(player_hp.hp + regen, player_hp.max_hp);
 	}
 
 	RunState::PlayerTurn
@@ -111,29 +225,30 @@ pub fn keyboard(gs: &mut State, ctx: &mut Rltk) -> RunState {
 		Some(key) => match key {
 			VirtualKeyCode::Numpad4 |
 				VirtualKeyCode::Left |
-				VirtualKeyCode::H => player_move(-1, 0, &mut gs.ecs),	// left
+				VirtualKeyCode::H => return player_move(-1, 0, ctx.shift, &mut gs.ecs),	// left
 			VirtualKeyCode::Numpad6 |
 				VirtualKeyCode::Right |
-				VirtualKeyCode::L => player_move(1, 0, &mut gs.ecs),	// right
+				VirtualKeyCode::L => return player_move(1, 0, ctx.shift, &mut gs.ecs),	// right
 			VirtualKeyCode::Numpad8 |
 				VirtualKeyCode::Up |
-				VirtualKeyCode::K => player_move(0, -1, &mut gs.ecs),	// up
+				VirtualKeyCode::K => return player_move(0, -1, ctx.shift, &mut gs.ecs),	// up
 			VirtualKeyCode::Numpad2 |
 				VirtualKeyCode::Down |
-				VirtualKeyCode::J => player_move(0, 1, &mut gs.ecs),	// down 
+				VirtualKeyCode::J => return player_move(0, 1, ctx.shift, &mut gs.ecs),	// down 
 			VirtualKeyCode::Numpad9 |
-				VirtualKeyCode::U => player_move(1, -1, &mut gs.ecs),	// up-right
+				VirtualKeyCode::U => return player_move(1, -1, ctx.shift, &mut gs.ecs),	// up-right
 			VirtualKeyCode::Numpad7 |
-				VirtualKeyCode::Y => player_move(-1, -1, &mut gs.ecs),	// up-left
+				VirtualKeyCode::Y => return player_move(-1, -1, ctx.shift, &mut gs.ecs),	// up-left
 			VirtualKeyCode::Numpad1 |
-				VirtualKeyCode::B => player_move(-1, 1, &mut gs.ecs),	// down-left
+				VirtualKeyCode::B => return player_move(-1, 1, ctx.shift, &mut gs.ecs),	// down-left
 			VirtualKeyCode::Numpad3 |
-				VirtualKeyCode::N => player_move(1, 1, &mut gs.ecs),	// down-right
+				VirtualKeyCode::N => return player_move(1, 1, ctx.shift, &mut gs.ecs),	// down-right
 			VirtualKeyCode::Numpad5 |
 				VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),	// skip turn
+			VirtualKeyCode::R => return ongoing_action::start(&mut gs.ecs, ActionKind::Rest),	// rest until healed
 			VirtualKeyCode::G => get_item(&mut gs.ecs),					// pickup item
-			VirtualKeyCode::I => return RunState::ShowInventory,
-			VirtualKeyCode::D => return RunState::ShowDropItem,
+			VirtualKeyCode::I => return RunState::ShowInventory{page: 0},
+			VirtualKeyCode::D => return RunState::ShowDropItem{page: 0},
 			VirtualKeyCode::Period => {
 				if climb_down(&mut gs.ecs) {
 					return RunState::NextLevel;
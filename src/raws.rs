@@ -0,0 +1,811 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::OnceLock;
+use rltk::{RGB, RandomNumberGenerator};
+use serde::{Deserialize, Deserializer, de::Error as _};
+use specs::prelude::*;
+use super::{CombatStats, Renderable, Name, BlocksTile, Position, Fov, Monster, Item, Potion, SpawnTable, Equippable,
+    EquipmentSlot, Abilities, Ability, AbilityEffect, MeleePowerBonus, DefenseBonus, UsesItems, Awareness,
+    AwarenessState, Boss, InBackpack, DiceRoll, WeaponDamage, DamageType, Resistances, XpValue, Knockback,
+    ProvidesFood, Stack, Weight, Scroll, MagicMapper, Teleports, Identifies, Ranged, AreaOfEffect, InflictsDamage,
+    Wand, Bolt, BoltEffect, Container, Trapped, Key, Gold, Merchant, Map, RemovesCurse, Friendly, identification::{self, ItemClass}};
+
+/// Where monster, item and spawn table definitions are read from at startup
+pub const RAW_PATH : &str = "raws/spawns.json";
+
+static RAWS : OnceLock<Raws> = OnceLock::new();
+
+/// Why the raw file could not be used
+#[derive(Debug)]
+pub struct RawError {
+    message : String
+}
+
+impl RawError {
+    fn new<S : ToString>(message : S) -> RawError {
+        RawError{message: message.to_string()}
+    }
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", RAW_PATH, self.message)
+    }
+}
+
+impl std::error::Error for RawError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Raws {
+    monsters : Vec<RawMonster>,
+    items : Vec<RawItem>,
+    spawn_table : Vec<RawSpawn>,
+    #[serde(default)]
+    affixes : Vec<Affix>,
+    #[serde(default)]
+    containers : Vec<RawContainer>,
+    gold : Option<RawGold>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRenderable {
+    #[serde(deserialize_with = "glyph")]
+    glyph : rltk::FontCharType,
+    #[serde(deserialize_with = "colour")]
+    fg : RGB,
+    #[serde(default = "black", deserialize_with = "colour")]
+    bg : RGB
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStats {
+    max_hp : i32,
+    defense : i32,
+    power : i32,
+    #[serde(deserialize_with = "dice")]
+    damage : DiceRoll,
+    damage_type : DamageType
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAbility {
+    name : String,
+    verb : String,
+    effect : AbilityEffect,
+    range : f32,
+    cooldown : i32
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWeight {
+    name : String,
+    weight : i32
+}
+
+/// Drops rolled on death, with `nothing` as the weight of dropping nothing
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawLoot {
+    #[serde(default)]
+    nothing : i32,
+    drops : Vec<RawWeight>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMonster {
+    name : String,
+    renderable : RawRenderable,
+    stats : RawStats,
+    xp : i32,
+    #[serde(default)]
+    uses_items : bool,
+    #[serde(default)]
+    abilities : Vec<RawAbility>,
+    resistances : Option<Resistances>,
+    loot : Option<RawLoot>,
+    /// Items the monster starts with in its backpack
+    #[serde(default)]
+    carries : Vec<String>,
+    /// Makes the monster a unique boss guarding this depth
+    boss_depth : Option<i32>,
+    /// Makes the monster a peaceful trader selling what it carries
+    merchant : Option<RawMerchant>,
+    /// Makes the creature harmless, so the player trades places with it instead of attacking
+    #[serde(default)]
+    friendly : bool
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMerchant {
+    /// Percent chance of setting up shop on a level
+    chance : i32,
+    #[serde(default)]
+    min_depth : i32
+}
+
+/// Coin piles, richer the deeper they lie
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGold {
+    name : String,
+    renderable : RawRenderable,
+    #[serde(deserialize_with = "dice")]
+    amount : DiceRoll,
+    #[serde(default)]
+    per_depth : i32
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPotion {
+    heal_amount : i32
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWeapon {
+    #[serde(deserialize_with = "dice")]
+    damage : DiceRoll,
+    kind : DamageType,
+    #[serde(default)]
+    knockback : i32
+}
+
+/// What reading a scroll does
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum RawScroll {
+    MagicMapping,
+    Teleport,
+    Identify,
+    RemoveCurse,
+    AreaDamage {
+        #[serde(deserialize_with = "dice")]
+        damage : DiceRoll,
+        kind : DamageType,
+        radius : i32,
+        range : i32
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum RawBolt {
+    Damage {
+        #[serde(deserialize_with = "dice")]
+        damage : DiceRoll,
+        kind : DamageType
+    },
+    Slow { turns : i32 },
+    Dig
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWand {
+    /// Rolled for each wand spawned
+    #[serde(deserialize_with = "dice")]
+    charges : DiceRoll,
+    #[serde(default)]
+    crumbles : bool,
+    range : i32,
+    bolt : RawBolt
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawItem {
+    name : String,
+    renderable : RawRenderable,
+    potion : Option<RawPotion>,
+    scroll : Option<RawScroll>,
+    wand : Option<RawWand>,
+    #[serde(default)]
+    food : bool,
+    #[serde(default)]
+    key : bool,
+    equippable : Option<EquipmentSlot>,
+    power_bonus : Option<i32>,
+    defense_bonus : Option<i32>,
+    weapon : Option<RawWeapon>,
+    resistances : Option<Resistances>,
+    /// Hides the item behind a random per-run appearance until it is identified
+    unidentified : Option<ItemClass>,
+    /// Pounds per item
+    #[serde(default)]
+    weight : f32,
+    /// Carried as one entity with a count rather than one entity each
+    #[serde(default)]
+    stackable : bool
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTrap {
+    name : String,
+    weight : i32,
+    #[serde(deserialize_with = "dice")]
+    damage : DiceRoll,
+    kind : DamageType
+}
+
+/// Chests and the like, filled from `loot` `rolls` times when spawned
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawContainer {
+    name : String,
+    renderable : RawRenderable,
+    #[serde(deserialize_with = "dice")]
+    rolls : DiceRoll,
+    loot : RawLoot,
+    /// Percent chance of spawning locked
+    #[serde(default)]
+    locked_chance : i32,
+    /// Percent chance of spawning with one of `traps`
+    #[serde(default)]
+    trap_chance : i32,
+    #[serde(default)]
+    traps : Vec<RawTrap>
+}
+
+/// A room spawn weight of `weight + per_depth * depth`, never below zero
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSpawn {
+    name : String,
+    weight : i32,
+    #[serde(default)]
+    per_depth : i32
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AffixPosition { Prefix, Suffix }
+
+/// A word magic equipment can roll into its name, and what it does to the item
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Affix {
+    pub name : String,
+    pub position : AffixPosition,
+    pub slots : Vec<EquipmentSlot>,
+    pub weight : i32,
+    #[serde(default)]
+    pub min_depth : i32,
+    #[serde(default)]
+    pub power : i32,
+    #[serde(default)]
+    pub defense : i32,
+    #[serde(default)]
+    pub knockback : i32,
+    /// Turns a weapon's damage into this type
+    pub damage_kind : Option<DamageType>,
+    /// Makes the wearer resist this type
+    pub resist : Option<DamageType>,
+    /// Multiplies the item's weight
+    pub weight_scale : Option<f32>
+}
+
+fn glyph<'de, D : Deserializer<'de>>(deserializer : D) -> Result<rltk::FontCharType, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(rltk::to_cp437(c)),
+        _ => Err(D::Error::custom(format!("glyph \"{}\" must be exactly one character", text)))
+    }
+}
+
+fn colour<'de, D : Deserializer<'de>>(deserializer : D) -> Result<RGB, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    RGB::from_hex(&text).map_err(|_| D::Error::custom(format!("colour \"{}\" is not a #RRGGBB hex code", text)))
+}
+
+fn black() -> RGB {
+    RGB::named(rltk::BLACK)
+}
+
+fn dice<'de, D : Deserializer<'de>>(deserializer : D) -> Result<DiceRoll, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_dice(&text).ok_or_else(|| D::Error::custom(format!("dice \"{}\" should look like 1d6 or 2d4+1", text)))
+}
+
+/// Reads dice written like 1d6, 2d4+1 or 1d8-1
+fn parse_dice(text : &str) -> Option<DiceRoll> {
+    let (n_dice, rest) = text.split_once('d')?;
+    let (die_type, bonus) = match rest.find(['+', '-']) {
+        None => (rest, 0),
+        Some(at) => (&rest[..at], rest[at..].trim_start_matches('+').parse().ok()?)
+    };
+    let (n_dice, die_type) : (i32, i32) = (n_dice.parse().ok()?, die_type.parse().ok()?);
+    if n_dice < 1 || die_type < 1 {return None;}
+    Some(DiceRoll::new(n_dice, die_type, bonus))
+}
+
+/// Reads and checks the raw file. Must succeed before anything is spawned.
+pub fn load() -> Result<(), RawError> {
+    let text = std::fs::read_to_string(RAW_PATH).map_err(|e| RawError::new(format!("could not read file: {}", e)))?;
+    let raws : Raws = serde_json::from_str(&text).map_err(RawError::new)?;
+    validate(&raws)?;
+    RAWS.set(raws).map_err(|_| RawError::new("loaded twice"))
+}
+
+/// Catches what serde cannot: duplicate or unknown names and contradictory entries
+fn validate(raws : &Raws) -> Result<(), RawError> {
+    let mut names = HashSet::new();
+    let all_names = raws.monsters.iter().map(|m| &m.name)
+        .chain(raws.items.iter().map(|i| &i.name))
+        .chain(raws.containers.iter().map(|c| &c.name))
+        .chain(raws.gold.iter().map(|g| &g.name));
+    for name in all_names {
+        if !names.insert(name.as_str()) {
+            return Err(RawError::new(format!("\"{}\" is defined more than once", name)));
+        }
+    }
+    // Gold can drop and fill chests like any item
+    let is_item = |name : &str| raws.items.iter().any(|item| item.name == name) || raws.gold.as_ref().is_some_and(|gold| gold.name == name);
+
+    for spawn in raws.spawn_table.iter() {
+        if !names.contains(spawn.name.as_str()) {
+            return Err(RawError::new(format!("spawn_table names unknown entity \"{}\"", spawn.name)));
+        }
+    }
+
+    let mut boss_depths = HashSet::new();
+    for monster in raws.monsters.iter() {
+        if monster.stats.max_hp < 1 {
+            return Err(RawError::new(format!("monster \"{}\" needs a max_hp of at least 1", monster.name)));
+        }
+        let loot = monster.loot.iter().flat_map(|loot| loot.drops.iter().map(|drop| &drop.name));
+        for item in loot.chain(monster.carries.iter()) {
+            if !is_item(item) {
+                return Err(RawError::new(format!("monster \"{}\" drops or carries unknown item \"{}\"", monster.name, item)));
+            }
+        }
+        if let Some(depth) = monster.boss_depth {
+            if !boss_depths.insert(depth) {
+                return Err(RawError::new(format!("more than one boss guards depth {}", depth)));
+            }
+        }
+        if let Some(merchant) = &monster.merchant {
+            if monster.boss_depth.is_some() {
+                return Err(RawError::new(format!("monster \"{}\" cannot be both a boss and a merchant", monster.name)));
+            }
+            if !(0..=100).contains(&merchant.chance) {
+                return Err(RawError::new(format!("merchant \"{}\" has a chance outside 0 to 100 percent", monster.name)));
+            }
+        }
+        if monster.friendly && (monster.boss_depth.is_some() || monster.merchant.is_some()) {
+            return Err(RawError::new(format!("monster \"{}\" cannot be friendly and also a boss or merchant", monster.name)));
+        }
+    }
+
+    for container in raws.containers.iter() {
+        if let Some(drop) = container.loot.drops.iter().find(|drop| !is_item(&drop.name)) {
+            return Err(RawError::new(format!("container \"{}\" holds unknown item \"{}\"", container.name, drop.name)));
+        }
+        let chances = [container.locked_chance, container.trap_chance];
+        if chances.iter().any(|chance| !(0..=100).contains(chance)) {
+            return Err(RawError::new(format!("container \"{}\" has a chance outside 0 to 100 percent", container.name)));
+        }
+        if container.trap_chance > 0 && container.traps.is_empty() {
+            return Err(RawError::new(format!("container \"{}\" can be trapped but lists no traps", container.name)));
+        }
+    }
+
+    let mut affix_names = HashSet::new();
+    for affix in raws.affixes.iter() {
+        if !affix_names.insert((affix.position, affix.name.as_str())) {
+            return Err(RawError::new(format!("affix \"{}\" is defined more than once", affix.name)));
+        }
+        if affix.slots.is_empty() || affix.weight < 1 {
+            return Err(RawError::new(format!("affix \"{}\" needs at least one slot and a weight of at least 1", affix.name)));
+        }
+        let effects = affix.power != 0 || affix.defense != 0 || affix.knockback != 0
+            || affix.damage_kind.is_some() || affix.resist.is_some() || affix.weight_scale.is_some();
+        if !effects {
+            return Err(RawError::new(format!("affix \"{}\" does nothing", affix.name)));
+        }
+    }
+
+    for item in raws.items.iter() {
+        let gear = item.weapon.is_some() || item.power_bonus.is_some() || item.defense_bonus.is_some() || item.resistances.is_some();
+        if gear && item.equippable.is_none() {
+            return Err(RawError::new(format!("item \"{}\" has equipment stats but no \"equippable\" slot", item.name)));
+        }
+        if item.stackable && item.equippable.is_some() {
+            return Err(RawError::new(format!("item \"{}\" is equippable and cannot also be stackable", item.name)));
+        }
+        if let Some(RawScroll::AreaDamage{radius, range, ..}) = item.scroll {
+            if radius < 0 || range < 1 {
+                return Err(RawError::new(format!("scroll \"{}\" needs a range of at least 1 and a radius of at least 0", item.name)));
+            }
+        }
+        if let Some(wand) = &item.wand {
+            if wand.range < 1 || item.stackable || item.scroll.is_some() {
+                return Err(RawError::new(format!("wand \"{}\" needs a range of at least 1 and cannot be stackable or a scroll", item.name)));
+            }
+        }
+        if item.weight < 0.0 {
+            return Err(RawError::new(format!("item \"{}\" cannot weigh less than nothing", item.name)));
+        }
+    }
+
+    for class in [ItemClass::Potion, ItemClass::Scroll, ItemClass::Wand] {
+        let hidden = raws.items.iter().filter(|item| item.unidentified == Some(class)).count();
+        let looks = identification::look_count(class);
+        if hidden > looks {
+            return Err(RawError::new(format!("{} unidentified {:?} items but only {} appearances to give them", hidden, class, looks)));
+        }
+    }
+
+    Ok(())
+}
+
+fn raws() -> &'static Raws {
+    RAWS.get().expect("raws::load must run before spawning")
+}
+
+/// Weighted chances for what appears in a room at this depth
+pub fn room_table(depth : i32) -> SpawnTable {
+    raws().spawn_table.iter().fold(SpawnTable::new(), |table, spawn| {
+        // Things still too deep for this level have a negative weight, which means they cannot spawn yet
+        table.add(&spawn.name, i32::max(0, spawn.weight + spawn.per_depth * depth))
+    })
+}
+
+/// What a monster may drop when it dies, if anything
+pub fn loot_table(monster : &str) -> Option<SpawnTable> {
+    raws().monsters.iter().find(|m| m.name == monster)?.loot.as_ref().map(spawn_table_of)
+}
+
+/// How many times to roll a container's loot table, and the table itself
+pub fn container_loot(container : &str) -> Option<(DiceRoll, SpawnTable)> {
+    let raw = raws().containers.iter().find(|c| c.name == container)?;
+    Some((raw.rolls, spawn_table_of(&raw.loot)))
+}
+
+fn spawn_table_of(loot : &RawLoot) -> SpawnTable {
+    loot.drops.iter().fold(SpawnTable::new().add("None", loot.nothing), |table, drop| table.add(&drop.name, drop.weight))
+}
+
+/// The unique boss guarding a depth, if any
+pub fn boss_for_depth(depth : i32) -> Option<&'static str> {
+    raws().monsters.iter().find(|m| m.boss_depth == Some(depth)).map(|m| m.name.as_str())
+}
+
+/// The merchants that set up shop on a level of this depth
+pub fn merchants_for_depth(depth : i32, rng : &mut RandomNumberGenerator) -> Vec<&'static str> {
+    raws().monsters.iter()
+        .filter(|m| m.merchant.as_ref().is_some_and(|merchant| merchant.min_depth <= depth && rng.roll_dice(1, 100) <= merchant.chance))
+        .map(|m| m.name.as_str())
+        .collect()
+}
+
+/// Every affix magic equipment can roll
+pub fn affixes() -> &'static [Affix] {
+    &raws().affixes
+}
+
+/// Items that go by a random appearance until identified
+pub fn unidentified_items() -> Vec<(&'static str, ItemClass)> {
+    raws().items.iter().filter_map(|item| item.unidentified.map(|class| (item.name.as_str(), class))).collect()
+}
+
+/// Spawns a monster or item by name, or None if the raws define no such thing
+pub fn spawn_named(ecs : &mut World, name : &str, x : i32, y : i32) -> Option<Entity> {
+    if let Some(monster) = raws().monsters.iter().find(|m| m.name == name) {
+        return Some(spawn_monster(ecs, monster, x, y));
+    }
+    if let Some(container) = raws().containers.iter().find(|c| c.name == name) {
+        return Some(spawn_container(ecs, container, x, y));
+    }
+    if let Some(gold) = raws().gold.as_ref().filter(|g| g.name == name) {
+        return Some(spawn_gold(ecs, gold, x, y));
+    }
+    raws().items.iter().find(|i| i.name == name).map(|item| spawn_item(ecs, item, x, y))
+}
+
+fn spawn_gold(ecs : &mut World, raw : &RawGold, x : i32, y : i32) -> Entity {
+    let depth = ecs.fetch::<Map>().depth;
+    let amount = raw.amount.roll(&mut ecs.write_resource::<RandomNumberGenerator>()) + raw.per_depth * depth;
+    ecs.create_entity()
+        .with(Position{x, y})
+        .with(renderable(&raw.renderable, 2))
+        .with(Name{name: raw.name.clone()})
+        .with(Gold{amount: i32::max(1, amount)})
+        .build()
+}
+
+/// An empty container, maybe locked or trapped. The spawner fills it.
+fn spawn_container(ecs : &mut World, raw : &RawContainer, x : i32, y : i32) -> Entity {
+    let (locked, trap) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let locked = rng.roll_dice(1, 100) <= raw.locked_chance;
+        let trap = if rng.roll_dice(1, 100) <= raw.trap_chance {
+            let roll = raw.traps.iter().fold(SpawnTable::new(), |table, trap| table.add(&trap.name, trap.weight)).roll(&mut rng);
+            raw.traps.iter().find(|trap| trap.name == roll)
+        } else {
+            None
+        };
+        (locked, trap)
+    };
+
+    let mut builder = ecs.create_entity()
+        .with(Position{x, y})
+        .with(renderable(&raw.renderable, 2))
+        .with(Name{name: raw.name.clone()})
+        .with(BlocksTile{})
+        .with(Container{locked});
+    if let Some(trap) = trap {
+        builder = builder.with(Trapped{name: trap.name.clone(), damage: trap.damage, kind: trap.kind});
+    }
+    builder.build()
+}
+
+fn renderable(raw : &RawRenderable, render_order : i32) -> Renderable {
+    Renderable{glyph: raw.glyph, fg: raw.fg, bg: raw.bg, render_order}
+}
+
+fn spawn_monster(ecs : &mut World, raw : &RawMonster, x : i32, y : i32) -> Entity {
+    // Most monsters have not noticed the player yet, and some are asleep. Bosses wait, awake.
+    // Merchants keep an eye on their customers, and friendly creatures have nothing to hide from.
+    let state = if raw.merchant.is_some() || raw.friendly {
+        AwarenessState::Aware
+    } else if raw.boss_depth.is_some() {
+        AwarenessState::Unaware
+    } else {
+        match ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 3) {
+            1 => AwarenessState::Asleep,
+            2 => AwarenessState::Unaware,
+            _ => AwarenessState::Aware
+        }
+    };
+
+    let stats = &raw.stats;
+    let mut builder = ecs.create_entity()
+        .with(Position{x, y})
+        .with(renderable(&raw.renderable, 1))
+        .with(Fov::new(8))
+        .with(Name{name: raw.name.clone()})
+        .with(BlocksTile{})
+        .with(CombatStats{max_hp: stats.max_hp, hp: stats.max_hp, defense: stats.defense, power: stats.power,
+            damage: stats.damage, damage_type: stats.damage_type})
+        .with(XpValue{xp: raw.xp})
+        .with(Awareness{state});
+
+    if raw.uses_items {
+        builder = builder.with(UsesItems{});
+    }
+    if !raw.abilities.is_empty() {
+        let list = raw.abilities.iter().map(|ability| Ability{
+            name: ability.name.clone(),
+            verb: ability.verb.clone(),
+            effect: ability.effect,
+            range: ability.range,
+            cooldown: ability.cooldown,
+            turns_until_ready: 0
+        }).collect();
+        builder = builder.with(Abilities{list});
+    }
+    if let Some(resistances) = &raw.resistances {
+        builder = builder.with(resistances.clone());
+    }
+    if raw.boss_depth.is_some() {
+        builder = builder.with(Boss{});
+    }
+    builder = if raw.merchant.is_some() {
+        builder.with(Merchant{})
+    } else if raw.friendly {
+        builder.with(Friendly{})
+    } else {
+        builder.with(Monster{})
+    };
+    let monster = builder.build();
+
+    // Repeated stackable items share one stack
+    let mut carried : Vec<(&str, Entity)> = Vec::new();
+    for name in raw.carries.iter() {
+        if let Some((_name, stack)) = carried.iter().find(|(carried_name, _)| carried_name == name) {
+            if let Some(stack) = ecs.write_storage::<Stack>().get_mut(*stack) {
+                stack.count += 1;
+                continue;
+            }
+        }
+        if let Some(item) = spawn_named(ecs, name, x, y) {
+            ecs.write_storage::<Position>().remove(item);
+            ecs.write_storage::<InBackpack>().insert(item, InBackpack{owner: monster}).expect("Unable to insert loot");
+            carried.push((name, item));
+        }
+    }
+    monster
+}
+
+fn spawn_item(ecs : &mut World, raw : &RawItem, x : i32, y : i32) -> Entity {
+    let charges = raw.wand.as_ref().map(|wand| wand.charges.roll(&mut ecs.write_resource::<RandomNumberGenerator>()));
+    let mut builder = ecs.create_entity()
+        .with(Position{x, y})
+        .with(renderable(&raw.renderable, 2))
+        .with(Name{name: raw.name.clone()})
+        .with(Item{})
+        .with(Weight{weight: raw.weight});
+
+    if raw.stackable {
+        builder = builder.with(Stack{count: 1});
+    }
+    if let Some(potion) = &raw.potion {
+        builder = builder.with(Potion{heal_amount: potion.heal_amount});
+    }
+    if let Some(scroll) = &raw.scroll {
+        builder = builder.with(Scroll{});
+        builder = match *scroll {
+            RawScroll::MagicMapping => builder.with(MagicMapper{}),
+            RawScroll::Teleport => builder.with(Teleports{}),
+            RawScroll::Identify => builder.with(Identifies{}),
+            RawScroll::RemoveCurse => builder.with(RemovesCurse{}),
+            RawScroll::AreaDamage{damage, kind, radius, range} => builder
+                .with(InflictsDamage{damage, kind})
+                .with(AreaOfEffect{radius})
+                .with(Ranged{range})
+        };
+    }
+    if let (Some(wand), Some(charges)) = (&raw.wand, charges) {
+        let effect = match wand.bolt {
+            RawBolt::Damage{damage, kind} => BoltEffect::Damage{damage, kind},
+            RawBolt::Slow{turns} => BoltEffect::Slow{turns},
+            RawBolt::Dig => BoltEffect::Dig
+        };
+        builder = builder
+            .with(Wand{charges: i32::max(1, charges), crumbles: wand.crumbles})
+            .with(Bolt{effect})
+            .with(Ranged{range: wand.range});
+    }
+    if raw.food {
+        builder = builder.with(ProvidesFood{});
+    }
+    if raw.key {
+        builder = builder.with(Key{});
+    }
+    if let Some(slot) = raw.equippable {
+        builder = builder.with(Equippable{slot});
+    }
+    if let Some(power) = raw.power_bonus {
+        builder = builder.with(MeleePowerBonus{power});
+    }
+    if let Some(defense) = raw.defense_bonus {
+        builder = builder.with(DefenseBonus{defense});
+    }
+    if let Some(weapon) = &raw.weapon {
+        builder = builder.with(WeaponDamage{dice: weapon.damage, kind: weapon.kind});
+        if weapon.knockback > 0 {
+            builder = builder.with(Knockback{distance: weapon.knockback});
+        }
+    }
+    if let Some(resistances) = &raw.resistances {
+        builder = builder.with(resistances.clone());
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// The smallest raw file that passes validation
+    fn minimal() -> Value {
+        json!({
+            "monsters": [{
+                "name": "Rat",
+                "renderable": { "glyph": "r", "fg": "#FF0000" },
+                "stats": { "max_hp": 4, "defense": 0, "power": 1, "damage": "1d3", "damage_type": "physical" },
+                "xp": 1
+            }],
+            "items": [{ "name": "Health Potion", "renderable": { "glyph": "!", "fg": "#FF00FF" }, "potion": { "heal_amount": 8 } }],
+            "spawn_table": [{ "name": "Rat", "weight": 1 }, { "name": "Health Potion", "weight": 1 }]
+        })
+    }
+
+    /// Validates the raws, returning the error message if there is one
+    fn error(raws : Value) -> Option<String> {
+        let raws : Raws = serde_json::from_value(raws).expect("Test raws should deserialize");
+        validate(&raws).err().map(|e| e.message)
+    }
+
+    /// Deserializes raws that should not get as far as validation, returning why
+    fn parse_error(raws : Value) -> String {
+        serde_json::from_value::<Raws>(raws).err().expect("Test raws should fail to deserialize").to_string()
+    }
+
+    #[test]
+    fn parses_dice() {
+        assert_eq!(parse_dice("1d6"), Some(DiceRoll::new(1, 6, 0)));
+        assert_eq!(parse_dice("2d4+1"), Some(DiceRoll::new(2, 4, 1)));
+        assert_eq!(parse_dice("1d8-1"), Some(DiceRoll::new(1, 8, -1)));
+    }
+
+    #[test]
+    fn rejects_dice_without_a_count() {
+        assert_eq!(parse_dice("d6"), None);
+        assert_eq!(parse_dice("0d6"), None);
+    }
+
+    #[test]
+    fn rejects_bad_glyph() {
+        let mut raws = minimal();
+        raws["monsters"][0]["renderable"]["glyph"] = json!("rat");
+        assert!(parse_error(raws).contains("glyph \"rat\" must be exactly one character"));
+    }
+
+    #[test]
+    fn rejects_bad_colour() {
+        let mut raws = minimal();
+        raws["items"][0]["renderable"]["fg"] = json!("pink");
+        assert!(parse_error(raws).contains("colour \"pink\" is not a #RRGGBB hex code"));
+    }
+
+    #[test]
+    fn rejects_bad_dice() {
+        let mut raws = minimal();
+        raws["monsters"][0]["stats"]["damage"] = json!("d6");
+        assert!(parse_error(raws).contains("dice \"d6\" should look like 1d6 or 2d4+1"));
+    }
+
+    #[test]
+    fn rejects_unknown_field() {
+        let mut raws = minimal();
+        raws["monsters"][0]["speed"] = json!(2);
+        assert!(parse_error(raws).contains("unknown field `speed`"));
+    }
+
+    #[test]
+    fn accepts_minimal_raws() {
+        assert_eq!(error(minimal()), None);
+    }
+
+    #[test]
+    fn rejects_unknown_spawn_name() {
+        let mut raws = minimal();
+        raws["spawn_table"][0]["name"] = json!("Dragon");
+        assert_eq!(error(raws), Some("spawn_table names unknown entity \"Dragon\"".to_string()));
+    }
+
+    #[test]
+    fn rejects_duplicate_name() {
+        let mut raws = minimal();
+        raws["items"][0]["name"] = json!("Rat");
+        assert_eq!(error(raws), Some("\"Rat\" is defined more than once".to_string()));
+    }
+
+    #[test]
+    fn rejects_unknown_loot_item() {
+        let mut raws = minimal();
+        raws["monsters"][0]["loot"] = json!({ "drops": [{ "name": "Cheese", "weight": 1 }] });
+        assert_eq!(error(raws), Some("monster \"Rat\" drops or carries unknown item \"Cheese\"".to_string()));
+    }
+
+    #[test]
+    fn rejects_more_unidentified_items_than_appearances() {
+        let mut raws = minimal();
+        let potions : Vec<Value> = (0..81).map(|i| json!({
+            "name": format!("Potion {}", i), "renderable": { "glyph": "!", "fg": "#FF00FF" }, "unidentified": "potion"
+        })).collect();
+        raws["items"] = json!(potions);
+        raws["spawn_table"] = json!([{ "name": "Rat", "weight": 1 }]);
+        assert_eq!(error(raws), Some("81 unidentified Potion items but only 80 appearances to give them".to_string()));
+    }
+
+    #[test]
+    fn rejects_friendly_merchant() {
+        let mut raws = minimal();
+        raws["monsters"][0]["friendly"] = json!(true);
+        raws["monsters"][0]["merchant"] = json!({ "chance": 50 });
+        assert_eq!(error(raws), Some("monster \"Rat\" cannot be friendly and also a boss or merchant".to_string()));
+    }
+}
\ No newline at end of file
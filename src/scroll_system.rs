@@ -0,0 +1,181 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator, DijkstraMap, RGB};
+use super::{WantsToReadScroll, Name, Map, TileType, Position, Fov, BlocksTile, InBackpack, Stack, CombatStats, SufferDamage,
+    MagicMapper, Teleports, Identifies, AreaOfEffect, InflictsDamage, gamelog::Gamelog, identification::ItemNames,
+    particle_system::ParticleBuilder, inventory_system::{use_up, witnessed}, RemovesCurse, CurseStatus, Blessing, Equipped,
+    Equippable, MeleePowerBonus, DefenseBonus, magic_items::{adjust_enchantment, CURSE_PENALTY}};
+
+/// How far a teleport may search for somewhere to land
+const TELEPORT_SEARCH_DEPTH : f32 = 1000.0;
+
+/// Carries out whatever scrolls were read this turn, then uses them up
+pub struct ScrollSystem {}
+
+impl<'a> System<'a> for ScrollSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Map>,
+                        WriteExpect<'a, Point>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteExpect<'a, ItemNames>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteStorage<'a, WantsToReadScroll>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, Fov>,
+                        ReadStorage<'a, BlocksTile>,
+                        ReadStorage<'a, InBackpack>,
+                        WriteStorage<'a, Stack>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ( ReadStorage<'a, MagicMapper>,
+                          ReadStorage<'a, Teleports>,
+                          ReadStorage<'a, Identifies>,
+                          ReadStorage<'a, AreaOfEffect>,
+                          ReadStorage<'a, InflictsDamage>,
+                          ReadStorage<'a, RemovesCurse> ),
+                        ( WriteStorage<'a, CurseStatus>,
+                          ReadStorage<'a, Equipped>,
+                          ReadStorage<'a, Equippable>,
+                          WriteStorage<'a, MeleePowerBonus>,
+                          WriteStorage<'a, DefenseBonus> ));
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, mut map, mut player_pos, mut gamelog, mut item_names, mut particles, mut rng,
+            mut wants_read, names, mut positions, mut fovs, blockers, backpack, mut stacks, combat_stats, mut suffer_damage,
+            (mappers, teleports, identifies, areas, damages, uncursers),
+            (mut curses, equipped, equippable, mut power_bonus, mut defense_bonus)) = data;
+
+        for (reader, read) in (&entities, &wants_read).join() {
+            let scroll = read.scroll;
+            let real_name = names.get(scroll).unwrap().name.clone();
+            let is_player = reader == *player_entity;
+            let seen = is_player || witnessed(&map, positions.get(reader));
+            let reader_name = names.get(reader).map_or("Something", |n| n.name.as_str());
+
+            if is_player {
+                let known_as = item_names.display(&real_name);
+                gamelog.entries.push(format!("You read the {}.", known_as));
+                if item_names.identify(&real_name) {
+                    gamelog.entries.push(format!("The {} was a {}!", known_as, real_name));
+                }
+            } else if seen {
+                gamelog.entries.push(format!("{} reads a {}.", reader_name, item_names.display(&real_name)));
+            }
+
+            if mappers.get(scroll).is_some() {
+                map.revealed_tiles.iter_mut().for_each(|tile| *tile = true);
+                let mut found_passage = false;
+                for tile in map.tiles.iter_mut().filter(|tile| **tile == TileType::HiddenStairs) {
+                    *tile = TileType::DownStairs;
+                    found_passage = true;
+                }
+                if is_player {
+                    gamelog.entries.push("The layout of the level floods into your mind.".to_string());
+                    if found_passage {
+                        gamelog.entries.push("You sense a hidden passage leading down!".to_string());
+                    }
+                }
+            }
+
+            if identifies.get(scroll).is_some() {
+                for (item, _pack) in (&entities, &backpack).join().filter(|(item, pack)| pack.owner == reader && *item != scroll) {
+                    let name = &names.get(item).unwrap().name;
+                    let known_as = item_names.display(name);
+                    if item_names.identify(name) && is_player {
+                        gamelog.entries.push(format!("The {} is a {}.", known_as, name));
+                    }
+                }
+                // Curses and blessings on what is carried or worn come to light too
+                for (item, curse) in (&entities, &mut curses).join() {
+                    let owned = backpack.get(item).is_some_and(|pack| pack.owner == reader)
+                        || equipped.get(item).is_some_and(|worn| worn.owner == reader);
+                    if owned && !curse.known && is_player {
+                        curse.known = true;
+                        let state = if curse.blessing == Blessing::Cursed {"cursed"} else {"blessed"};
+                        gamelog.entries.push(format!("The {} is {}.", names.get(item).unwrap().name, state));
+                    }
+                }
+            }
+
+            if uncursers.get(scroll).is_some() {
+                let cursed : Vec<Entity> = (&entities, &curses).join()
+                    .filter(|(item, curse)| curse.blessing == Blessing::Cursed && (
+                        backpack.get(*item).is_some_and(|pack| pack.owner == reader)
+                        || equipped.get(*item).is_some_and(|worn| worn.owner == reader)))
+                    .map(|(item, _curse)| item)
+                    .collect();
+                for item in cursed.iter() {
+                    curses.remove(*item);
+                    if let Some(equippable) = equippable.get(*item) {
+                        adjust_enchantment(*item, equippable.slot, CURSE_PENALTY, &mut power_bonus, &mut defense_bonus);
+                    }
+                }
+                if is_player {
+                    gamelog.entries.push(if cursed.is_empty() {
+                        "You feel as if someone is watching over you.".to_string()
+                    } else {
+                        "A weight lifts from your belongings. The curses are gone.".to_string()
+                    });
+                }
+            }
+
+            if teleports.get(scroll).is_some() {
+                if let Some(pos) = positions.get_mut(reader) {
+                    let start = map.xy_idx(pos.x, pos.y);
+                    let reachable = DijkstraMap::new(map.width, map.height, &[start], &*map, TELEPORT_SEARCH_DEPTH);
+                    let landings : Vec<usize> = (0..map.tiles.len())
+                        .filter(|idx| *idx != start && map.tiles[*idx] == TileType::Floor && !map.blocked[*idx] && reachable.map[*idx] < f32::MAX)
+                        .collect();
+                    match rng.random_slice_entry(&landings) {
+                        None => if is_player {gamelog.entries.push("You feel a tug, but nothing happens.".to_string())},
+                        Some(landing) => {
+                            particles.flash(pos.x, pos.y, RGB::named(rltk::MAGENTA));
+                            map.move_entity(reader, start, *landing, blockers.get(reader).is_some());
+                            pos.x = *landing as i32 % map.width;
+                            pos.y = *landing as i32 / map.width;
+                            if let Some(fov) = fovs.get_mut(reader) {
+                                fov.dirty = true;
+                            }
+                            if is_player {
+                                player_pos.x = pos.x;
+                                player_pos.y = pos.y;
+                                gamelog.entries.push("The world lurches and you find yourself elsewhere.".to_string());
+                            } else if seen {
+                                gamelog.entries.push(format!("{} vanishes!", reader_name));
+                            }
+                        }
+                    }
+                }
+            }
+
+            if let (Some(damage), Some(target)) = (damages.get(scroll), read.target) {
+                let radius = areas.get(scroll).map_or(0, |area| area.radius);
+                let mut blast = rltk::field_of_view(target, radius, &*map);
+                blast.retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+                if !blast.contains(&target) {
+                    blast.push(target);
+                }
+
+                let amount = damage.damage.roll(&mut rng);
+                for tile in blast.iter() {
+                    particles.flash(tile.x, tile.y, RGB::named(rltk::ORANGE));
+                    for victim in map.tile_content[map.xy_idx(tile.x, tile.y)].iter() {
+                        if combat_stats.get(*victim).is_none() {continue;}
+                        SufferDamage::new_damage(&mut suffer_damage, *victim, amount, damage.kind, Some(reader), &real_name);
+                        if witnessed(&map, Some(&Position{x: tile.x, y: tile.y})) {
+                            let victim_name = names.get(*victim).map_or("Something", |n| n.name.as_str());
+                            gamelog.entries.push(format!("{} is caught in the blast for {} {} damage.", victim_name, amount, damage.kind.name()));
+                        }
+                    }
+                }
+            }
+
+            use_up(&entities, &mut stacks, scroll);
+        }
+
+        wants_read.clear();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,152 @@
+use specs::prelude::*;
+use super::{Name, InBackpack, Purse, Merchant, Monster, Stack, Potion, ProvidesFood, Key, Scroll, InflictsDamage, AreaOfEffect,
+    Wand, WeaponDamage, MeleePowerBonus, DefenseBonus, Knockback, Resistances, DiceRoll, AttackIntent, CurseStatus, Equippable,
+    EquipmentSlot, gamelog::Gamelog, identification::{ItemNames, ItemClass}, magic_items::blessing_shift};
+
+/// Merchants buy things back for this fraction of what they sell them for
+const RESALE_DIVISOR : i32 = 2;
+
+fn average(dice : &DiceRoll) -> i32 {
+    dice.n_dice * (dice.die_type + 1) / 2 + dice.bonus
+}
+
+/// What an unidentified item goes for. Everything of a class costs the same, so the price gives nothing away.
+fn class_value(class : ItemClass) -> i32 {
+    match class {
+        ItemClass::Potion => 20,
+        ItemClass::Scroll => 25,
+        ItemClass::Wand => 40
+    }
+}
+
+/// What one of an item is worth, judged from what it does. Unidentified items and curses or
+/// blessings nobody has found out about yet are left out, or the price would give them away.
+fn unit_value(ecs : &World, item : Entity) -> i32 {
+    let hidden_class = ecs.read_storage::<Name>().get(item).and_then(|name| ecs.fetch::<ItemNames>().hidden_class(&name.name));
+    if let Some(class) = hidden_class {
+        return class_value(class);
+    }
+    let hidden = ecs.read_storage::<CurseStatus>().get(item).filter(|curse| !curse.known).map_or(0, |curse| blessing_shift(curse.blessing));
+    let slot = ecs.read_storage::<Equippable>().get(item).map(|equippable| equippable.slot);
+    let mut value = 0;
+    if let Some(potion) = ecs.read_storage::<Potion>().get(item) {
+        value += potion.heal_amount * 3;
+    }
+    if ecs.read_storage::<ProvidesFood>().get(item).is_some() {
+        value += 10;
+    }
+    if ecs.read_storage::<Key>().get(item).is_some() {
+        value += 15;
+    }
+    if ecs.read_storage::<Scroll>().get(item).is_some() {
+        value += 25;
+    }
+    if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(item) {
+        value += average(&damage.damage) * 3;
+    }
+    if let Some(area) = ecs.read_storage::<AreaOfEffect>().get(item) {
+        value += area.radius * 10;
+    }
+    if let Some(wand) = ecs.read_storage::<Wand>().get(item) {
+        value += 20 + wand.charges * 10;
+    }
+    if let Some(weapon) = ecs.read_storage::<WeaponDamage>().get(item) {
+        value += average(&weapon.dice) * 6;
+    }
+    if let Some(bonus) = ecs.read_storage::<MeleePowerBonus>().get(item) {
+        let shift = if slot == Some(EquipmentSlot::Weapon) {hidden} else {0};
+        value += (bonus.power - shift) * 15;
+    }
+    if let Some(bonus) = ecs.read_storage::<DefenseBonus>().get(item) {
+        let shift = if slot == Some(EquipmentSlot::Shield) {hidden} else {0};
+        value += (bonus.defense - shift) * 20;
+    }
+    if let Some(knockback) = ecs.read_storage::<Knockback>().get(item) {
+        value += knockback.distance * 15;
+    }
+    if let Some(resists) = ecs.read_storage::<Resistances>().get(item) {
+        value += (resists.resistant.len() as i32) * 20 + (resists.immune.len() as i32) * 50;
+    }
+    i32::max(1, value)
+}
+
+/// What a merchant asks for a whole stack of an item
+pub fn buy_price(ecs : &World, item : Entity) -> i32 {
+    let count = ecs.read_storage::<Stack>().get(item).map_or(1, |stack| stack.count);
+    unit_value(ecs, item) * count
+}
+
+/// What a merchant pays for a whole stack of an item
+pub fn sell_price(ecs : &World, item : Entity) -> i32 {
+    i32::max(1, buy_price(ecs, item) / RESALE_DIVISOR)
+}
+
+/// Moves an item into another backpack, joining a stack of the same kind already there
+fn hand_over(ecs : &mut World, item : Entity, to : Entity) {
+    let entities = ecs.entities();
+    let names = ecs.read_storage::<Name>();
+    let mut stacks = ecs.write_storage::<Stack>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+
+    let name = &names.get(item).unwrap().name;
+    let same_stack = stacks.get(item).and_then(|_stack| (&entities, &backpack, &names, &stacks).join()
+        .find(|(other, pack, other_name, _stack)| *other != item && pack.owner == to && other_name.name == *name)
+        .map(|(other, ..)| other));
+    match same_stack {
+        Some(other) => {
+            let count = stacks.get(item).unwrap().count;
+            stacks.get_mut(other).unwrap().count += count;
+            entities.delete(item).expect("Unable to merge stack");
+        }
+        None => {
+            backpack.insert(item, InBackpack{owner: to}).expect("Unable to hand over item");
+        }
+    }
+}
+
+fn item_name(ecs : &World, item : Entity) -> String {
+    let names = ecs.read_storage::<Name>();
+    ecs.fetch::<ItemNames>().display(&names.get(item).unwrap().name)
+}
+
+/// Buys an item from the merchant's stock, if the player can afford it
+pub fn buy(ecs : &mut World, merchant : Entity, item : Entity) {
+    let player_entity = *ecs.fetch::<Entity>();
+    if ecs.read_storage::<InBackpack>().get(item).is_none_or(|pack| pack.owner != merchant) {return;}
+    let price = buy_price(ecs, item);
+    let name = item_name(ecs, item);
+    {
+        let mut purses = ecs.write_storage::<Purse>();
+        let Some(purse) = purses.get_mut(player_entity) else {return};
+        if purse.gold < price {
+            ecs.fetch_mut::<Gamelog>().entries.push(format!("You cannot afford the {} ({} gold).", name, price));
+            return;
+        }
+        purse.gold -= price;
+    }
+    hand_over(ecs, item, player_entity);
+    ecs.fetch_mut::<Gamelog>().entries.push(format!("You buy the {} for {} gold.", name, price));
+}
+
+/// Sells one of the player's items, the whole stack of it, to the merchant
+pub fn sell(ecs : &mut World, merchant : Entity, item : Entity) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let price = sell_price(ecs, item);
+    let name = item_name(ecs, item);
+    match ecs.write_storage::<Purse>().get_mut(player_entity) {
+        Some(purse) => purse.gold += price,
+        None => return
+    }
+    hand_over(ecs, item, merchant);
+    ecs.fetch_mut::<Gamelog>().entries.push(format!("You sell the {} for {} gold.", name, price));
+}
+
+/// The player attacks a merchant on purpose, after which it fights back like any monster
+pub fn provoke(ecs : &mut World, merchant : Entity) {
+    let player_entity = *ecs.fetch::<Entity>();
+    ecs.write_storage::<Merchant>().remove(merchant);
+    ecs.write_storage::<Monster>().insert(merchant, Monster{}).expect("Unable to insert monster");
+    ecs.write_storage::<AttackIntent>().insert(player_entity, AttackIntent{target: merchant}).expect("Unable to insert attack");
+    let name = ecs.read_storage::<Name>().get(merchant).map_or("The merchant".to_string(), |n| n.name.clone());
+    ecs.fetch_mut::<Gamelog>().entries.push(format!("{} turns on you!", name));
+}
\ No newline at end of file
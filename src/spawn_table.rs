@@ -31,14 +31,12 @@ impl SpawnTable {
     pub fn roll(&self, rng : &mut RandomNumberGenerator) -> String {
         if self.total_weight == 0 {return "None".to_string();}
         let mut roll = rng.roll_dice(1, self.total_weight)-1;
-        let mut idx : usize = 0;
 
-        while roll > 0 {
-            if roll < self.entries[idx].weight {
-                return self.entries[idx].name.clone();
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return entry.name.clone();
             }
-            roll -= self.entries[idx].weight;
-            idx += 1;
+            roll -= entry.weight;
         }
 
         "None".to_string()
@@ -1,11 +1,25 @@
 use rltk::{RGB, RandomNumberGenerator};
 use specs::prelude::*;
-use super::{CombatStats, Player, Renderable, Name, BlocksTile, Position, Fov,
-    Monster, Rect, map::MAPWIDTH, Item, Potion, SpawnTable, Equippable, EquipmentSlot};
+use super::{CombatStats, Player, Renderable, Name, Position, Fov, Monster, Rect, map::MAPWIDTH, Pack, Map,
+    gamelog::Gamelog, DiceRoll, DamageType, Experience, Perks, Corpse, HungerClock, HungerState, Encumbrance,
+    hunger_system::WELL_FED_TURNS, raws, magic_items, InContainer, Purse};
 use std::collections::HashMap;
 
 
 const MAX_MONSTERS : i32 = 4;
+const CORPSE_DECAY_TURNS : i32 = 40;
+
+/// Bosses already met this run, so each appears only once
+#[derive(Default)]
+pub struct BossTracker {
+    pub met : Vec<String>
+}
+
+/// Hands out pack ids. Entity ids get reused, so a new pack could otherwise share one with a surviving pack.
+#[derive(Default)]
+pub struct PackIds {
+    next : u32
+}
 
 /// Spawns player and returns that entity object
 pub fn player(ecs : &mut World, player_x : i32, player_y : i32) -> Entity {
@@ -19,35 +33,56 @@ pub fn player(ecs : &mut World, player_x : i32, player_y : i32) -> Entity {
             render_order: 0
         })
         .with(Player{})
-        .with(Fov{visible_tiles : Vec::new(), range : 8, dirty : true})
+        .with(Fov::new(8))
         .with(Name{name: "Player".to_string()})
-        .with(CombatStats{max_hp: 30, hp: 30, defense: 2, power: 5})
+        .with(CombatStats{max_hp: 30, hp: 30, defense: 2, power: 5, damage: DiceRoll::new(1, 6, 0), damage_type: DamageType::Physical})
+        .with(Experience{level: 1, xp: 0, picks: 0})
+        .with(Perks::default())
+        .with(HungerClock{state: HungerState::WellFed, duration: WELL_FED_TURNS})
+        .with(Encumbrance::default())
+        .with(Purse::default())
         .build()
 }
 
 
-fn orc(ecs: &mut World, x:i32, y:i32) {monster(ecs, x, y, rltk::to_cp437('o'), "Orc");}
-fn goblin(ecs: &mut World, x:i32, y:i32) {monster(ecs, x, y, rltk::to_cp437('g'), "Goblin");}
+/// Fills every room but the first and the lair, and puts the depth's boss in its lair the first time it is reached
+pub fn spawn_level(ecs: &mut World, map: &Map) {
+    for room in map.rooms.iter().skip(1).filter(|room| map.boss_room != Some(**room)) {
+        spawn_room(ecs, room, map.depth);
+    }
+    spawn_merchants(ecs, map);
 
-fn monster<S : ToString>(ecs: &mut World, x: i32, y:i32, glyph: rltk::FontCharType, name : S) {
-    ecs.create_entity()
-        .with(Position{x, y})
-        .with(Renderable{
-            glyph: glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 1
-        })
-        .with(Fov{visible_tiles : Vec::new(), range : 8, dirty : true})
-        .with(Monster{})
-        .with(Name{name: name.to_string()})
-        .with(BlocksTile{})
-        .with(CombatStats{max_hp: 16, hp: 16, defense: 1, power: 4})
-        .build();
+    if let (Some(lair), Some(name)) = (map.boss_room, raws::boss_for_depth(map.depth)) {
+        if ecs.fetch::<BossTracker>().met.iter().any(|met| met == name) {return;}
+        let (x, y) = lair.center();
+        spawn_entity(ecs, name, x, y);
+        ecs.write_resource::<BossTracker>().met.push(name.to_string());
+        ecs.write_resource::<Gamelog>().entries.push(format!("The air grows heavy. {} guards the way down.", name));
+    }
+}
+
+/// Sets up any merchants trading on this depth, each on a free tile of a room away from the start and the lair
+fn spawn_merchants(ecs: &mut World, map: &Map) {
+    let rooms : Vec<Rect> = map.rooms.iter().skip(1).filter(|room| map.boss_room != Some(**room)).copied().collect();
+    if rooms.is_empty() {return;}
+    let merchants = raws::merchants_for_depth(map.depth, &mut ecs.write_resource::<RandomNumberGenerator>());
+
+    for name in merchants {
+        let spot = {
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            let positions = ecs.read_storage::<Position>();
+            let room = rooms[rng.roll_dice(1, rooms.len() as i32) as usize - 1];
+            (0..20).map(|_| (room.x1 + rng.roll_dice(1, room.x2 - room.x1), room.y1 + rng.roll_dice(1, room.y2 - room.y1)))
+                .find(|(x, y)| !positions.join().any(|pos| pos.x == *x && pos.y == *y))
+        };
+        if let Some((x, y)) = spot {
+            spawn_entity(ecs, name, x, y);
+        }
+    }
 }
 
-pub fn spawn_room(ecs: &mut World, room: &Rect, depth: i32) {
-    let spawn_table = room_table(depth);
+fn spawn_room(ecs: &mut World, room: &Rect, depth: i32) {
+    let spawn_table = raws::room_table(depth);
     let mut spawn_points : HashMap<usize, String> = HashMap::new();
 
     // Scope
@@ -58,12 +93,12 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, depth: i32) {
         for _i in 0..num_spawns {
             let mut added = false;
             let mut tries = 0;
-            while !added {
+            while !added && tries < 20 {
                 let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
                 let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
                 let idx = (y * MAPWIDTH) + x;
-                if !spawn_points.contains_key(&idx) {
-                    spawn_points.insert(idx, spawn_table.roll(&mut rng));
+                if let std::collections::hash_map::Entry::Vacant(spot) = spawn_points.entry(idx) {
+                    spot.insert(spawn_table.roll(&mut rng));
                     added = true;
                 } else {
                     tries += 1;
@@ -76,70 +111,79 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, depth: i32) {
     for spawn in spawn_points.iter() {
         let x = (*spawn.0 % MAPWIDTH) as i32;
         let y = (*spawn.0 / MAPWIDTH) as i32;
-    
-        match spawn.1.as_ref() {
-            "Goblin" => goblin(ecs, x, y),
-            "Orc" => orc(ecs, x, y),
-            "Health Potion" => health_potion(ecs, x, y),
-            "Shield" => shield(ecs, x, y),
-            "Dagger" => dagger(ecs, x, y),
-            _ => {}
+        if let Some(entity) = spawn_entity(ecs, spawn.1, x, y) {
+            magic_items::roll_magic(ecs, entity, depth);
+            fill_container(ecs, entity, spawn.1, depth);
+        }
+    }
+
+    // Monsters spawned in the same room hunt as a pack
+    let pack : Vec<Entity> = {
+        let entities = ecs.entities();
+        let monsters = ecs.read_storage::<Monster>();
+        let positions = ecs.read_storage::<Position>();
+        (&entities, &monsters, &positions).join()
+            .filter(|(_e, _m, pos)| spawn_points.contains_key(&((pos.y as usize * MAPWIDTH) + pos.x as usize)))
+            .map(|(e, _m, _p)| e)
+            .collect()
+    };
+    if pack.len() > 1 {
+        let id = {
+            let mut ids = ecs.write_resource::<PackIds>();
+            ids.next += 1;
+            ids.next
+        };
+        let mut packs = ecs.write_storage::<Pack>();
+        for member in pack.iter() {
+            packs.insert(*member, Pack{id}).expect("Unable to insert pack");
         }
     }
 }
 
-/// Spawns health potion
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position{x, y})
-        .with(Renderable{
-            glyph: rltk::to_cp437('!'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        })
-        .with(Name{name: "Health Potion".to_string()})
-        .with(Item{})
-        .with(Potion{heal_amount: 8})
-        .build();
+/// Spawns a monster or item by its name in the raws
+pub fn spawn_entity(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    raws::spawn_named(ecs, name, x, y)
 }
 
-fn dagger(ecs: &mut World, x: i32, y: i32) {
+/// Leaves the remains of a fallen monster, tinted like it was
+pub fn corpse(ecs: &mut World, x: i32, y: i32, name: &str, fg: RGB) -> Entity {
     ecs.create_entity()
-        .with(Position{x,y})
+        .with(Position{x, y})
         .with(Renderable{
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::YELLOW),
+            glyph: rltk::to_cp437('%'),
+            fg,
             bg: RGB::named(rltk::BLACK),
-            render_order: 2
+            render_order: 3
         })
-        .with(Name{name : "Dagger".to_string()})
-        .with(Equippable{slot: EquipmentSlot::Weapon})
-        .with(Item{})
-        .build();
+        .with(Name{name: format!("{} corpse", name)})
+        .with(Corpse{turns_left: CORPSE_DECAY_TURNS})
+        .build()
 }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position{x,y})
-        .with(Renderable{
-            glyph: rltk::to_cp437(']'),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        })
-        .with(Name{name: "Shield".to_string()})
-        .with(Equippable{slot: EquipmentSlot::Shield})
-        .with(Item{})
-        .build();
+/// Rolls a container's loot table and puts what comes up inside it
+fn fill_container(ecs: &mut World, container: Entity, name: &str, depth: i32) {
+    let Some((rolls, table)) = raws::container_loot(name) else {return};
+    let contents : Vec<String> = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let count = rolls.roll(&mut rng);
+        (0..count).map(|_| table.roll(&mut rng)).collect()
+    };
+    for roll in contents.iter() {
+        let Some(item) = spawn_entity(ecs, roll, 0, 0) else {continue};
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InContainer>().insert(item, InContainer{container}).expect("Unable to insert into container");
+        magic_items::roll_magic(ecs, item, depth);
+    }
 }
 
-/// Gives weighted chances for spawns in room
-fn room_table(depth: i32) -> SpawnTable {
-    SpawnTable::new()
-        .add("Goblin", 11)
-        .add("Orc", 1 + depth)
-        .add("Health Potion", 7)
-        .add("Shield", 3)
-        .add("Dagger", 3)
+/// Rolls what a monster drops when it dies, on top of what it carried
+pub fn roll_loot(ecs: &mut World, monster: &str, x: i32, y: i32) -> Option<Entity> {
+    let roll = match raws::loot_table(monster) {
+        None => return None,
+        Some(table) => table.roll(&mut ecs.write_resource::<RandomNumberGenerator>())
+    };
+    let depth = ecs.fetch::<Map>().depth;
+    let item = spawn_entity(ecs, &roll, x, y)?;
+    magic_items::roll_magic(ecs, item, depth);
+    Some(item)
 }
\ No newline at end of file
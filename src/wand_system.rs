@@ -0,0 +1,132 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator, LineAlg, RGB};
+use super::{WantsToZap, Wand, Bolt, BoltEffect, Ranged, Name, Map, TileType, Position, CombatStats, SufferDamage, Slowed, Fov,
+    gamelog::Gamelog, identification::ItemNames, particle_system::ParticleBuilder, inventory_system::witnessed};
+
+/// Fires wands along a Bresenham line until the bolt meets a creature or a wall, spending a charge each time
+pub struct WandSystem {}
+
+impl<'a> System<'a> for WandSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Map>,
+                        WriteExpect<'a, Gamelog>,
+                        WriteExpect<'a, ItemNames>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteStorage<'a, WantsToZap>,
+                        WriteStorage<'a, Wand>,
+                        ReadStorage<'a, Bolt>,
+                        ReadStorage<'a, Ranged>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, Slowed>,
+                        WriteStorage<'a, Fov>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, mut map, mut gamelog, mut item_names, mut particles, mut rng, mut wants_zap,
+            mut wands, bolts, ranged, names, positions, combat_stats, mut suffer_damage, mut slowed, mut fovs) = data;
+
+        for (zapper, zap) in (&entities, &wants_zap).join() {
+            let (Some(wand), Some(bolt), Some(pos)) = (wands.get_mut(zap.wand), bolts.get(zap.wand), positions.get(zapper)) else {continue};
+            let real_name = names.get(zap.wand).unwrap().name.clone();
+            let is_player = zapper == *player_entity;
+            let seen = is_player || witnessed(&map, Some(pos));
+            let zapper_name = names.get(zapper).map_or("Something", |n| n.name.as_str());
+
+            if wand.charges < 1 {
+                if is_player {
+                    gamelog.entries.push(format!("You wave the {}, but nothing happens.", item_names.display(&real_name)));
+                }
+                continue;
+            }
+            wand.charges -= 1;
+
+            if is_player {
+                let known_as = item_names.display(&real_name);
+                gamelog.entries.push(format!("You zap the {}.", known_as));
+                if item_names.identify(&real_name) {
+                    gamelog.entries.push(format!("The {} was a {}!", known_as, real_name));
+                }
+            } else if seen {
+                gamelog.entries.push(format!("{} zaps a {}!", zapper_name, item_names.display(&real_name)));
+            }
+
+            // The bolt keeps going past the aimed at tile until it runs out of range
+            let start = Point::new(pos.x, pos.y);
+            let range = ranged.get(zap.wand).map_or(1, |r| r.range);
+            let (dx, dy) = (zap.target.x - start.x, zap.target.y - start.y);
+            let steps = i32::max(dx.abs(), dy.abs());
+            if steps == 0 {continue;}
+            let reach = (range + steps - 1) / steps;
+            let far = Point::new(start.x + dx * reach, start.y + dy * reach);
+
+            let mut end = start;
+            let mut struck : Option<Entity> = None;
+            let mut dug = false;
+            for point in rltk::line2d(LineAlg::Bresenham, start, far).into_iter().skip(1).take(range as usize) {
+                if point.x < 1 || point.x >= map.width - 1 || point.y < 1 || point.y >= map.height - 1 {break;}
+                let idx = map.xy_idx(point.x, point.y);
+                if map.tiles[idx] == TileType::Wall {
+                    if !matches!(bolt.effect, BoltEffect::Dig) {break;}
+                    map.tiles[idx] = TileType::Floor;
+                    map.blocked[idx] = false;
+                    dug = true;
+                }
+                end = point;
+                struck = map.tile_content[idx].iter().find(|entity| combat_stats.get(**entity).is_some()).copied();
+                if struck.is_some() {break;}
+            }
+            // A new opening in the rock changes what everyone can see
+            if dug {
+                for fov in (&mut fovs).join() {
+                    fov.dirty = true;
+                }
+            }
+
+            let colour = match bolt.effect {
+                BoltEffect::Damage{..} => RGB::named(rltk::CYAN),
+                BoltEffect::Slow{..} => RGB::named(rltk::PURPLE),
+                BoltEffect::Dig => RGB::named(rltk::SANDY_BROWN)
+            };
+            particles.trail(start, end, colour, rltk::to_cp437('*'));
+            let end_seen = witnessed(&map, Some(&Position{x: end.x, y: end.y}));
+
+            match (bolt.effect, struck) {
+                (BoltEffect::Damage{damage, kind}, Some(victim)) => {
+                    let amount = damage.roll(&mut rng);
+                    SufferDamage::new_damage(&mut suffer_damage, victim, amount, kind, Some(zapper), &real_name);
+                    particles.flash(end.x, end.y, colour);
+                    if end_seen {
+                        let victim_name = names.get(victim).map_or("Something", |n| n.name.as_str());
+                        gamelog.entries.push(format!("The bolt hits {} for {} {} damage.", victim_name, amount, kind.name()));
+                    }
+                }
+                (BoltEffect::Slow{turns}, Some(victim)) => {
+                    let current = slowed.get(victim).map_or(0, |slow| slow.turns);
+                    slowed.insert(victim, Slowed{turns: i32::max(current, turns)}).expect("Unable to insert slow");
+                    if end_seen {
+                        let victim_name = names.get(victim).map_or("Something", |n| n.name.as_str());
+                        gamelog.entries.push(format!("{} slows down.", victim_name));
+                    }
+                }
+                (BoltEffect::Dig, _) if dug && (seen || end_seen) => {
+                    gamelog.entries.push("The rock crumbles away before the bolt.".to_string());
+                }
+                _ => {}
+            }
+
+            if wand.charges < 1 && wand.crumbles {
+                entities.delete(zap.wand).expect("Unable to delete wand");
+                if seen {
+                    gamelog.entries.push(format!("The {} crumbles to dust.", item_names.display(&real_name)));
+                }
+            }
+        }
+
+        wants_zap.clear();
+    }
+}
\ No newline at end of file